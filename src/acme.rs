@@ -0,0 +1,934 @@
+use crate::certificate;
+use crate::error;
+use crate::error::AcmeError;
+use crate::error::FileError;
+use crate::error::Warning;
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+use inquire::Select;
+use inquire::Text;
+use inquire::error::CustomUserError;
+use inquire::validator::Validation;
+
+use rcgen::CertificateParams;
+use rcgen::KeyPair;
+use rcgen::PKCS_ECDSA_P256_SHA256;
+
+use reqwest::header;
+
+use ring::digest;
+use ring::rand::SystemRandom;
+use ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+use ring::signature::EcdsaKeyPair;
+use ring::signature::KeyPair as _;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use serde_json::Value;
+use serde_json::json;
+
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// How often the state of an authorization or an order is checked, and how many times.
+/// Certificate authorities only validate HTTP-01 challenges on this port.
+pub const HTTP_01_PORT: u16 = 80;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const POLL_ATTEMPTS: usize = 60;
+
+/// Everything needed to obtain a certificate again, stored with the server so that it can be renewed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Acme {
+    /// The directory URL of the certificate authority.
+    pub directory: String,
+
+    /// An email address the certificate authority may use to reach the account holder.
+    pub contact: Option<String>,
+
+    pub domains: Vec<String>,
+
+    pub challenge: Challenge,
+
+    /// Extra PEM root certificates to trust when talking to the directory, e.g. Pebble's.
+    pub root_certificates: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Challenge {
+    /// Answered by the CLI itself on port 80, which a server on that port has to make room on.
+    Http01,
+
+    /// Answered by an executable that publishes TXT records.
+    ///
+    /// It is called as `hook present <record> <value>` before validation and `hook cleanup <record> <value>`
+    /// afterwards, and must only return from `present` once the record is visible to the certificate authority.
+    Dns01 { hook: PathBuf },
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http01 => f.write_str("HTTP-01 on port 80"),
+
+            Self::Dns01 { .. } => f.write_str("DNS-01 through a hook"),
+        }
+    }
+}
+
+impl Acme {
+    /// Only offers the DNS-01 challenge for servers on a remote host, since the CLI answers HTTP-01 challenges itself.
+    pub fn inquire(is_local: bool) -> Result<Option<Self>, AcmeError> {
+        let Some(directory) = Text::new("Which ACME directory would you like to use?")
+            //
+            .with_default(LETS_ENCRYPT_DIRECTORY)
+            //
+            .prompt_skippable()
+            //
+            .map_err(AcmeError::prompt("ACME directory"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(root_certificates) = Text::new("Which extra root certificates should be trusted for this directory?")
+            //
+            .with_help_message("Enter the path to a PEM bundle, e.g. for a local Pebble, or leave empty.")
+            //
+            .prompt_skippable()
+            //
+            .map_err(AcmeError::prompt("ACME root certificates"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(contact) = Text::new("Which email address should the certificate authority use to reach you?")
+            //
+            .with_help_message("Leave empty to register without a contact.")
+            //
+            .prompt_skippable()
+            //
+            .map_err(AcmeError::prompt("ACME contact"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(domains) = Text::new("Which domains should the certificate cover?")
+            //
+            .with_help_message("Separate them with commas.")
+            //
+            .with_validator(|input: &str| -> Result<Validation, CustomUserError> {
+                Ok(certificate::parse_subject_alt_names(input)
+                    //
+                    .map(|_| Validation::Valid)
+                    //
+                    .unwrap_or_else(|error| Validation::Invalid(format!("{} 😣", error::describe(&error)).into())))
+            })
+            //
+            .prompt_skippable()
+            //
+            .map_err(AcmeError::prompt("ACME domains"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let mut options = vec![
+            //
+            Challenge::Http01,
+            //
+            Challenge::Dns01 { hook: PathBuf::new() },
+        ];
+
+        if !is_local {
+            options.retain(|challenge| !matches!(challenge, Challenge::Http01));
+        }
+
+        let mut prompt = Select::new("How should the domains be validated?", options);
+
+        if !is_local {
+            prompt = prompt.with_help_message("HTTP-01 is only offered for servers on this machine, which answers it.");
+        }
+
+        let Some(mut challenge) = prompt
+            //
+            .prompt_skippable()
+            //
+            .map_err(AcmeError::prompt("ACME challenge"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        if let Challenge::Dns01 { hook } = &mut challenge {
+            let Some(path) = Text::new("Please enter the path to your DNS hook:")
+                //
+                .prompt_skippable()
+                //
+                .map_err(AcmeError::prompt("DNS hook"))?
+            //
+            else {
+                return Ok(None);
+            };
+
+            *hook = PathBuf::from(path);
+        }
+
+        Ok(Some(Self {
+            directory,
+
+            contact: Some(contact).filter(|contact| !contact.is_empty()),
+
+            domains: certificate::parse_subject_alt_names(&domains).map_err(AcmeError::Domains)?,
+
+            challenge,
+
+            root_certificates: Some(root_certificates)
+                //
+                .filter(|path| !path.is_empty())
+                //
+                .map(PathBuf::from),
+        }))
+    }
+}
+
+/// Obtains a certificate and its private key, both PEM encoded.
+///
+/// With the HTTP-01 challenge, [`HTTP_01_PORT`] must be free: the CLI answers the challenge there itself. What goes
+/// wrong without failing, e.g. a DNS record that could not be cleaned up, is handed to `on_warning`.
+pub async fn obtain(acme: &Acme, on_warning: &dyn Fn(Warning)) -> Result<(String, String), AcmeError> {
+    let mut client = Client::try_new(acme)
+        //
+        .await
+        //
+        .map_err(AcmeError::step("failed to reach the ACME directory"))?;
+
+    client
+        //
+        .register(acme.contact.as_deref())
+        //
+        .await
+        //
+        .map_err(AcmeError::step("failed to register the ACME account"))?;
+
+    let identifiers = acme
+        //
+        .domains
+        //
+        .iter()
+        //
+        .map(|domain| json!({ "type": "dns", "value": domain }))
+        //
+        .collect::<Vec<_>>();
+
+    let new_order = client.directory.new_order.clone();
+
+    let response = client
+        //
+        .post(&new_order, Some(json!({ "identifiers": identifiers })))
+        //
+        .await
+        //
+        .map_err(AcmeError::step("failed to place an order"))?;
+
+    let order_url = response
+        //
+        .headers()
+        //
+        .get(header::LOCATION)
+        //
+        .and_then(|value| value.to_str().ok())
+        //
+        .ok_or_else(|| AcmeError::Protocol("the order has no location".to_string()))?
+        //
+        .to_string();
+
+    let order = response
+        //
+        .json::<Order>()
+        //
+        .await
+        //
+        .map_err(AcmeError::http("failed to read the order"))?;
+
+    for authorization_url in &order.authorizations {
+        client
+            //
+            .authorize(authorization_url, &acme.challenge, on_warning)
+            //
+            .await
+            //
+            .map_err(AcmeError::step(format!(
+                "failed to complete the authorization at {authorization_url}"
+            )))?;
+    }
+
+    let key_pair = KeyPair::generate()
+        //
+        .map_err(AcmeError::key("failed to generate a key pair"))?;
+
+    let csr = CertificateParams::new(acme.domains.clone())
+        //
+        .map_err(AcmeError::key("not a valid list of domains"))?
+        //
+        .serialize_request(&key_pair)
+        //
+        .map_err(AcmeError::key("failed to create a certificate signing request"))?;
+
+    client
+        //
+        .post(
+            &order.finalize,
+            Some(json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })),
+        )
+        //
+        .await
+        //
+        .map_err(AcmeError::step("failed to finalize the order"))?;
+
+    let mut certificate_url = None;
+
+    for _ in 0..POLL_ATTEMPTS {
+        let order = client
+            //
+            .fetch::<Order>(&order_url)
+            //
+            .await
+            //
+            .map_err(AcmeError::step("failed to read the order"))?;
+
+        match order.status.as_str() {
+            "valid" => {
+                certificate_url = order.certificate;
+
+                break;
+            }
+
+            "invalid" => {
+                return Err(AcmeError::InvalidOrder(Problem::describe(order.error)));
+            }
+
+            _ => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    let certificate_url = certificate_url
+        //
+        .ok_or(AcmeError::Timeout("order"))?;
+
+    let chain = client
+        //
+        .post(&certificate_url, None)
+        //
+        .await
+        //
+        .map_err(AcmeError::step("failed to download the certificate"))?
+        //
+        .text()
+        //
+        .await
+        //
+        .map_err(AcmeError::http("failed to read the certificate"))?;
+
+    Ok((chain, key_pair.serialize_pem()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+
+    new_account: String,
+
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+
+    #[serde(default)]
+    authorizations: Vec<String>,
+
+    #[serde(default)]
+    finalize: String,
+
+    certificate: Option<String>,
+
+    error: Option<Problem>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+
+    identifier: Identifier,
+
+    challenges: Vec<ChallengeObject>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct ChallengeObject {
+    #[serde(rename = "type")]
+    typ: String,
+
+    url: String,
+
+    #[serde(default)]
+    token: String,
+
+    error: Option<Problem>,
+}
+
+/// An RFC 7807 problem document, as returned by ACME servers on errors.
+#[derive(Deserialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    typ: String,
+
+    #[serde(default)]
+    detail: String,
+}
+
+impl Problem {
+    fn describe(problem: Option<Self>) -> String {
+        problem
+            //
+            .map(|Self { typ, detail }| format!("{detail} ({typ})"))
+            //
+            .unwrap_or_else(|| "no details were given".to_string())
+    }
+}
+
+struct Client {
+    http: reqwest::Client,
+
+    key_pair: EcdsaKeyPair,
+
+    directory: Directory,
+
+    nonce: Option<String>,
+
+    /// The account URL, which identifies the key once the account is registered.
+    account: Option<String>,
+}
+
+impl Client {
+    async fn try_new(acme: &Acme) -> Result<Self, AcmeError> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(path) = &acme.root_certificates {
+            let pem = fs::read(path)
+                //
+                .map_err(FileError::on("read", path))?;
+
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)
+                //
+                .map_err(AcmeError::http("failed to parse the root certificates"))?
+            {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        let http = builder
+            //
+            .build()
+            //
+            .map_err(AcmeError::http("failed to build the http client"))?;
+
+        let directory = http
+            //
+            .get(&acme.directory)
+            //
+            .send()
+            //
+            .await
+            //
+            .map_err(AcmeError::http("failed to send http request"))?
+            //
+            .error_for_status()
+            //
+            .map_err(AcmeError::http("the directory answered with an error"))?
+            //
+            .json::<Directory>()
+            //
+            .await
+            //
+            .map_err(AcmeError::http("failed to receive http response"))?;
+
+        let key_pair = account_key(&acme.directory)
+            //
+            .map_err(AcmeError::step("failed to load the account key"))?;
+
+        Ok(Self {
+            http,
+
+            key_pair,
+
+            directory,
+
+            nonce: None,
+
+            account: None,
+        })
+    }
+
+    async fn register(&mut self, contact: Option<&str>) -> Result<(), AcmeError> {
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+
+        if let Some(contact) = contact {
+            payload["contact"] = json!([format!("mailto:{contact}")]);
+        }
+
+        let new_account = self.directory.new_account.clone();
+
+        // registering an existing key returns the existing account
+        let response = self
+            //
+            .post(&new_account, Some(payload))
+            //
+            .await?;
+
+        let account = response
+            //
+            .headers()
+            //
+            .get(header::LOCATION)
+            //
+            .and_then(|value| value.to_str().ok())
+            //
+            .ok_or_else(|| AcmeError::Protocol("the account has no location".to_string()))?;
+
+        self.account = Some(account.to_string());
+
+        Ok(())
+    }
+
+    async fn authorize(
+        &mut self,
+        url: &str,
+        challenge: &Challenge,
+        on_warning: &dyn Fn(Warning),
+    ) -> Result<(), AcmeError> {
+        let Authorization {
+            //
+            status,
+            //
+            identifier,
+            //
+            challenges,
+        } = self
+            //
+            .fetch::<Authorization>(url)
+            //
+            .await?;
+
+        if status == "valid" {
+            return Ok(());
+        }
+
+        let typ = match challenge {
+            Challenge::Http01 => "http-01",
+
+            Challenge::Dns01 { .. } => "dns-01",
+        };
+
+        let ChallengeObject {
+            //
+            url: challenge_url,
+            //
+            token,
+            ..
+        } = challenges
+            //
+            .into_iter()
+            //
+            .find(|object| object.typ == typ)
+            //
+            .ok_or_else(|| {
+                AcmeError::Protocol(format!(
+                    "the certificate authority does not offer {typ} for {}",
+                    identifier.value
+                ))
+            })?;
+
+        let key_authorization = format!("{token}.{}", self.thumbprint());
+
+        match challenge {
+            Challenge::Http01 => {
+                let listener = TcpListener::bind(("0.0.0.0", HTTP_01_PORT))
+                    //
+                    .await
+                    //
+                    .map_err(|source| AcmeError::Listen {
+                        port: HTTP_01_PORT,
+
+                        source,
+                    })?;
+
+                let responder = tokio::spawn(serve_http_01(listener, token, key_authorization));
+
+                let result = self.validate(url, &challenge_url).await;
+
+                responder.abort();
+
+                result
+            }
+
+            Challenge::Dns01 { hook } => {
+                let record = format!("_acme-challenge.{}", identifier.value.trim_start_matches("*."));
+
+                let value = URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, key_authorization.as_bytes()));
+
+                run_hook(hook, "present", &record, &value)?;
+
+                let result = self.validate(url, &challenge_url).await;
+
+                // a record left behind does no harm, unlike losing why the validation failed
+                if let Err(error) = run_hook(hook, "cleanup", &record, &value) {
+                    on_warning(Warning::RecordLeft { record, error });
+                }
+
+                result
+            }
+        }
+    }
+
+    /// Tells the certificate authority that the challenge is ready, then waits for the verdict.
+    async fn validate(&mut self, authorization_url: &str, challenge_url: &str) -> Result<(), AcmeError> {
+        self.post(challenge_url, Some(json!({})))
+            //
+            .await
+            //
+            .map_err(AcmeError::step("failed to respond to the challenge"))?;
+
+        for _ in 0..POLL_ATTEMPTS {
+            let Authorization {
+                //
+                status,
+                //
+                challenges,
+                ..
+            } = self
+                //
+                .fetch::<Authorization>(authorization_url)
+                //
+                .await?;
+
+            match status.as_str() {
+                "valid" => {
+                    return Ok(());
+                }
+
+                "pending" => {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+
+                _ => {
+                    let error = challenges
+                        //
+                        .into_iter()
+                        //
+                        .find_map(|object| object.error);
+
+                    return Err(AcmeError::FailedAuthorization {
+                        status,
+
+                        problem: Problem::describe(error),
+                    });
+                }
+            }
+        }
+
+        Err(AcmeError::Timeout("authorization"))
+    }
+
+    async fn fetch<T>(&mut self, url: &str) -> Result<T, AcmeError>
+    where
+        T: DeserializeOwned,
+    {
+        self.post(url, None)
+            //
+            .await?
+            //
+            .json::<T>()
+            //
+            .await
+            //
+            .map_err(AcmeError::http("failed to receive http response"))
+    }
+
+    async fn nonce(&mut self) -> Result<String, AcmeError> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let response = self
+            //
+            .http
+            //
+            .head(&self.directory.new_nonce)
+            //
+            .send()
+            //
+            .await
+            //
+            .map_err(AcmeError::http("failed to send http request"))?;
+
+        response
+            //
+            .headers()
+            //
+            .get("replay-nonce")
+            //
+            .and_then(|value| value.to_str().ok())
+            //
+            .map(ToString::to_string)
+            //
+            .ok_or_else(|| AcmeError::Protocol("the directory did not return a nonce".to_string()))
+    }
+
+    /// Sends a signed request, or a "POST-as-GET" when there is no payload.
+    async fn post(&mut self, url: &str, payload: Option<Value>) -> Result<reqwest::Response, AcmeError> {
+        // a nonce may be rejected once, e.g. when the server restarted in between
+        for attempt in 0..2 {
+            let nonce = self.nonce().await?;
+
+            let body = self.sign(url, &nonce, payload.as_ref());
+
+            let response = self
+                //
+                .http
+                //
+                .post(url)
+                //
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                //
+                .body(body.to_string())
+                //
+                .send()
+                //
+                .await
+                //
+                .map_err(AcmeError::http("failed to send http request"))?;
+
+            self.nonce = response
+                //
+                .headers()
+                //
+                .get("replay-nonce")
+                //
+                .and_then(|value| value.to_str().ok())
+                //
+                .map(ToString::to_string);
+
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let problem = response
+                //
+                .json::<Problem>()
+                //
+                .await
+                //
+                .ok();
+
+            if let Some(Problem { typ, .. }) = &problem
+                && typ == "urn:ietf:params:acme:error:badNonce"
+                && attempt == 0
+            {
+                continue;
+            }
+
+            return Err(AcmeError::Refused(Problem::describe(problem)));
+        }
+
+        unreachable!()
+    }
+
+    fn sign(&self, url: &str, nonce: &str, payload: Option<&Value>) -> Value {
+        let mut protected = json!({
+            "alg": "ES256",
+            "nonce": nonce,
+            "url": url,
+        });
+
+        if let Some(account) = &self.account {
+            protected["kid"] = json!(account);
+        } else {
+            protected["jwk"] = self.jwk();
+        }
+
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+
+        let payload = payload
+            //
+            .map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string()))
+            //
+            .unwrap_or_default();
+
+        let signature = self
+            //
+            .key_pair
+            //
+            .sign(&SystemRandom::new(), format!("{protected}.{payload}").as_bytes())
+            //
+            .expect("signing with a valid key should not fail");
+
+        json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        })
+    }
+
+    fn jwk(&self) -> Value {
+        // an uncompressed P-256 point: 0x04, then 32 bytes of x and 32 bytes of y
+        let public_key = self.key_pair.public_key().as_ref();
+
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+        })
+    }
+
+    /// The RFC 7638 thumbprint of the account key, which the challenges are bound to.
+    fn thumbprint(&self) -> String {
+        // serde_json sorts object keys, which is exactly the canonical form the RFC requires
+        let jwk = self.jwk().to_string();
+
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, jwk.as_bytes()))
+    }
+}
+
+/// Loads the account key for a directory from the data directory, creating it on first use.
+fn account_key(directory: &str) -> Result<EcdsaKeyPair, AcmeError> {
+    let data_dir = dirs::data_dir()
+        //
+        .ok_or(AcmeError::NoDataDirectory)?;
+
+    let accounts = data_dir.join("mayo").join("acme");
+
+    let digest = digest::digest(&digest::SHA256, directory.as_bytes());
+
+    let path = accounts.join(format!("{}.key", URL_SAFE_NO_PAD.encode(digest)));
+
+    let key_pair = if path.exists() {
+        let pem = fs::read_to_string(&path)
+            //
+            .map_err(FileError::on("read", &path))?;
+
+        KeyPair::from_pem(&pem)
+            //
+            .map_err(AcmeError::key("failed to parse the account key"))?
+    } else {
+        let key_pair = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)
+            //
+            .map_err(AcmeError::key("failed to generate a key pair"))?;
+
+        fs::create_dir_all(&accounts)
+            //
+            .map_err(FileError::on("create", &accounts))?;
+
+        certificate::write_private(&path, &key_pair.serialize_pem())?;
+
+        key_pair
+    };
+
+    EcdsaKeyPair::from_pkcs8(
+        //
+        &ECDSA_P256_SHA256_FIXED_SIGNING,
+        //
+        &key_pair.serialize_der(),
+        //
+        &SystemRandom::new(),
+    )
+    .map_err(|error| AcmeError::KeyType(error.to_string()))
+}
+
+async fn serve_http_01(listener: TcpListener, token: String, key_authorization: String) {
+    let path = format!("/.well-known/acme-challenge/{token}");
+
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let mut buffer = [0; 4096];
+
+        let Ok(length) = stream.read(&mut buffer).await else {
+            continue;
+        };
+
+        let request = String::from_utf8_lossy(&buffer[..length]);
+
+        let requested_path = request
+            //
+            .split_whitespace()
+            //
+            .nth(1)
+            //
+            .unwrap_or_default();
+
+        let response = if requested_path == path {
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                key_authorization.len(),
+                key_authorization
+            )
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        };
+
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+}
+
+fn run_hook(hook: &PathBuf, action: &str, record: &str, value: &str) -> Result<(), AcmeError> {
+    let status = process::Command::new(hook)
+        //
+        .args([action, record, value])
+        //
+        .status()
+        //
+        .map_err(|source| AcmeError::Hook {
+            hook: hook.clone(),
+
+            source,
+        })?;
+
+    if !status.success() {
+        return Err(AcmeError::HookFailed {
+            hook: hook.clone(),
+
+            action: action.to_string(),
+
+            status,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,341 @@
+//! Taking over app containers that were created by hand rather than by the CLI.
+//!
+//! The parameters of a server are reconstructed from what the CLI would have set itself: the environment variables
+//! the app reads, the data mount and the library mounts. Whatever else the container was given is reported instead of
+//! being silently dropped.
+
+use crate::error::AdoptError;
+use crate::labels::*;
+use crate::mayo::MAYO_DATA_PATH;
+use crate::parameters::Authentication;
+use crate::parameters::Environment;
+use crate::parameters::Libraries;
+use crate::parameters::Library;
+use crate::parameters::Name;
+use crate::parameters::Parameters;
+use crate::parameters::Port;
+use crate::parameters::Security;
+use crate::parameters::Storage;
+use crate::parameters::Tls;
+use crate::registry;
+use crate::versioning;
+
+use std::path::PathBuf;
+
+use bollard::models::*;
+
+use semver::Version;
+
+/// A container, and the server it becomes once adopted.
+#[derive(Clone, Debug)]
+pub struct Adoption {
+    pub container_id: String,
+
+    pub container_name: String,
+
+    pub is_running: bool,
+
+    pub parameters: Parameters,
+
+    /// A data volume named otherwise than the server's own, whose data is copied into the server's volume.
+    pub copy_from: Option<String>,
+
+    /// Settings of the container that the server will not have.
+    pub unmapped: Vec<String>,
+}
+
+/// Reconstructs the parameters of a server from an inspected container.
+///
+/// `image_tags` are the tags of the container's image, and `image_env` its default environment, which is left out of
+/// the server's. The server is named `name`, or after the container without its `mayo-` prefix.
+pub fn map_container(
+    container: ContainerInspectResponse,
+    image_tags: &[String],
+    image_env: &[String],
+    name: Option<Name>,
+) -> Result<Adoption, AdoptError> {
+    let ContainerInspectResponse {
+        //
+        id,
+        //
+        name: container_name,
+        //
+        state,
+        //
+        config,
+        //
+        host_config,
+        //
+        mounts,
+        ..
+    } = container;
+
+    let container_id = id.ok_or(AdoptError::MissingField("id"))?;
+
+    let container_name = container_name
+        //
+        .map(|name| name.trim_start_matches('/').to_string())
+        //
+        .ok_or(AdoptError::MissingField("name"))?;
+
+    let ContainerConfig {
+        //
+        image,
+        //
+        user,
+        //
+        env,
+        //
+        labels,
+        ..
+    } = config.ok_or(AdoptError::MissingField("config"))?;
+
+    if labels.is_some_and(|labels| labels.contains_key(LABEL_KEY_CLI_VERSION)) {
+        return Err(AdoptError::AlreadyManaged(container_name));
+    }
+
+    let image = image.unwrap_or_default();
+
+    let version = app_version(&image, image_tags)?;
+
+    let name = match name {
+        Some(name) => name,
+
+        None => {
+            let name = container_name
+                //
+                .strip_prefix("mayo-")
+                //
+                .unwrap_or(&container_name)
+                //
+                .to_string();
+
+            Name::try_new(name.clone()).map_err(|source| AdoptError::InvalidName { name, source })?
+        }
+    };
+
+    let mut unmapped = Vec::new();
+
+    let mut port = None;
+
+    let mut data_path = MAYO_DATA_PATH.to_string();
+
+    let mut crt = None;
+
+    let mut key = None;
+
+    let mut authentication = Authentication::new(false);
+
+    let mut environment = Environment::default();
+
+    for pair in env.unwrap_or_default() {
+        // the image's own variables come back with every container
+        if image_env.contains(&pair) {
+            continue;
+        }
+
+        let (key_name, value) = pair.split_once('=').unwrap_or((&pair, ""));
+
+        match key_name {
+            "BUN_PORT" => {
+                let value = value
+                    //
+                    .parse::<u16>()
+                    //
+                    .map_err(|_| AdoptError::InvalidPort(value.to_string()))?;
+
+                port = Some(Port::new(value));
+            }
+
+            "MAYO_DATA_PATH" => {
+                data_path = value.to_string();
+            }
+
+            "MAYO_TLS_CRT" => {
+                crt = Some(value.to_string());
+            }
+
+            "MAYO_TLS_KEY" => {
+                key = Some(value.to_string());
+            }
+
+            "MAYO_AUTHENTICATION" => {
+                authentication = Authentication::new(value == "required");
+            }
+
+            // rebuilt from the library mounts
+            "MAYO_LIBRARY_PATHS" => {
+                //
+            }
+
+            _ => {
+                if let Err(error) = environment.insert_pair(&pair) {
+                    unmapped.push(error.to_string());
+                }
+            }
+        }
+    }
+
+    let port = port.ok_or(AdoptError::MissingPort)?;
+
+    let tls = match (crt, key) {
+        (Some(crt), Some(key)) if crt.contains("-----BEGIN") && key.contains("-----BEGIN") => Tls::new(crt, key),
+
+        (Some(_), Some(_)) => {
+            unmapped.push("MAYO_TLS_CRT and MAYO_TLS_KEY, which are not PEM, e.g. paths".to_string());
+
+            Tls::disabled()
+        }
+
+        (Some(_), None) | (None, Some(_)) => {
+            unmapped.push("MAYO_TLS_CRT or MAYO_TLS_KEY, which is set without the other".to_string());
+
+            Tls::disabled()
+        }
+
+        (None, None) => Tls::disabled(),
+    };
+
+    let mut storage = None;
+
+    let mut copy_from = None;
+
+    let mut libraries = Libraries::default();
+
+    for mount in mounts.unwrap_or_default() {
+        let MountPoint {
+            //
+            typ,
+            //
+            name: volume,
+            //
+            source,
+            //
+            destination,
+            ..
+        } = mount;
+
+        let destination = destination.unwrap_or_default();
+
+        let source = source.unwrap_or_default();
+
+        match (typ, volume) {
+            (Some(MountPointTypeEnum::VOLUME), Some(volume)) if destination == data_path => {
+                if volume != Storage::Volume.source(&name) {
+                    copy_from = Some(volume);
+                }
+
+                storage = Some(Storage::Volume);
+            }
+
+            (Some(MountPointTypeEnum::BIND), _) if destination == data_path => {
+                storage = Some(Storage::Bind(PathBuf::from(source)));
+            }
+
+            (Some(MountPointTypeEnum::BIND), _) if destination.starts_with("/mayo/libraries/") => {
+                let library_name = destination.trim_start_matches("/mayo/libraries/").to_string();
+
+                let result = Library::try_new(library_name, PathBuf::from(&source))
+                    //
+                    .and_then(|library| libraries.add(library));
+
+                if let Err(error) = result {
+                    unmapped.push(format!("the library at {source}: {error}"));
+                }
+            }
+
+            _ => {
+                unmapped.push(format!("the mount of {source} at {destination}"));
+            }
+        }
+    }
+
+    let storage = storage.unwrap_or_else(|| {
+        unmapped.push(format!("the data in {data_path}, which is not on a mount"));
+
+        Storage::Volume
+    });
+
+    if let Some(user) = user.filter(|user| !user.is_empty()) {
+        unmapped.push(format!("the user {user}, servers run as the image's user"));
+    }
+
+    if let Some(host_config) = host_config {
+        let network_mode = host_config.network_mode.unwrap_or_default();
+
+        if network_mode != "host" && !network_mode.is_empty() {
+            unmapped.push(format!("the network {network_mode}, servers use the host network"));
+        }
+
+        for (port, _) in host_config.port_bindings.unwrap_or_default() {
+            unmapped.push(format!("the published port {port}, servers use the host network"));
+        }
+    }
+
+    let is_running = state
+        //
+        .and_then(|state| state.running)
+        //
+        .unwrap_or_default();
+
+    Ok(Adoption {
+        container_id,
+
+        container_name,
+
+        is_running,
+
+        parameters: Parameters {
+            name,
+
+            version,
+
+            port,
+
+            authentication,
+
+            tls,
+
+            security: Security::Standard,
+
+            storage,
+
+            libraries,
+
+            environment,
+
+            acme: None,
+        },
+
+        copy_from,
+
+        unmapped,
+    })
+}
+
+/// The newest compatible app version among the tags of the app's image.
+fn app_version(image: &str, image_tags: &[String]) -> Result<Version, AdoptError> {
+    let repository = format!("{}/{}/{}", registry::REGISTRY, registry::USERNAME, registry::REPOSITORY);
+
+    let tags = image_tags
+        //
+        .iter()
+        //
+        .filter_map(|repo_tag| repo_tag.split_once(':'))
+        //
+        .filter(|(repo, _)| *repo == repository)
+        //
+        .map(|(_, tag)| tag)
+        //
+        .collect::<Vec<_>>();
+
+    if tags.is_empty() {
+        return Err(AdoptError::NotAnApp(image.to_string()));
+    }
+
+    versioning::tags_to_compatible_app_versions(tags)
+        //
+        .max()
+        //
+        .ok_or_else(|| AdoptError::IncompatibleVersion(image.to_string()))
+}
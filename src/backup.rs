@@ -0,0 +1,411 @@
+//! Portable backups of a server's data.
+//!
+//! # Format
+//!
+//! A backup is a tar archive compressed with zstd, conventionally named `*.tar.zst`, that contains exactly two entries
+//! in this order:
+//!
+//! - `manifest.json`, a [`Manifest`] encoded as JSON;
+//! - `data.tar`, the contents of the server's data path as Docker's archive API returns them, i.e. a tar archive with a
+//!   single top-level `.data` directory.
+//!
+//! The manifest's `format` field is [`FORMAT_VERSION`] for archives written by this version of the CLI. It is bumped
+//! whenever either entry changes in a way older readers would misinterpret, and readers must refuse formats they do
+//! not know.
+//!
+//! The parameters in the manifest have their secrets removed: the TLS private key and the values of secret
+//! environment variables are empty strings.
+
+use crate::engine::Engine;
+use crate::error::BackupError;
+use crate::error::FileError;
+use crate::error::Result;
+use crate::error::ServerError;
+use crate::error::Warning;
+use crate::mayo::Mayo;
+use crate::parameters::Parameters;
+use crate::parameters::Storage;
+use crate::server::Server;
+use crate::versioning;
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use semver::Version;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+const DATA_ENTRY: &str = "data.tar";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub format: u32,
+
+    /// When the backup was taken, in RFC 3339 format.
+    pub created_at: String,
+
+    /// The version of the CLI that took the backup.
+    pub cli_version: Version,
+
+    /// The version of the app whose data this is.
+    pub app_version: Version,
+
+    pub parameters: Parameters,
+}
+
+/// Writes a backup of a server to `output`.
+///
+/// A running server is stopped while its data is copied, so that the copy is consistent, and started again afterwards.
+pub async fn create<E: Engine>(mayo: &Mayo<E>, server: &Server, output: &Path) -> Result<Manifest> {
+    server.check_writable()?;
+
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+
+        created_at: OffsetDateTime::now_utc()
+            //
+            .format(&Rfc3339)
+            //
+            .map_err(BackupError::Timestamp)?,
+
+        cli_version: versioning::current_cli_version(),
+
+        app_version: server.parameters.version.clone(),
+
+        parameters: server.parameters.without_secrets(),
+    };
+
+    // the outer archive needs the size of the data up front, so the data is staged next to the output
+    let staging_path = output.with_extension("data.partial");
+
+    let result = async {
+        let mut staging = File::options()
+            //
+            .read(true)
+            //
+            .write(true)
+            //
+            .create(true)
+            //
+            .truncate(true)
+            //
+            .open(&staging_path)
+            //
+            .map_err(FileError::on("create", &staging_path))?;
+
+        let is_running = server.state == "running";
+
+        if is_running {
+            mayo.stop_server(server).await?;
+        }
+
+        let download_result = mayo.download_data(&server.id, &mut staging).await;
+
+        if is_running {
+            mayo.start_server(server).await?;
+        }
+
+        download_result?;
+
+        staging
+            //
+            .seek(SeekFrom::Start(0))
+            //
+            .map_err(BackupError::io("failed to rewind the staged data"))?;
+
+        Ok(write_archive(output, &manifest, &mut staging)?)
+    }
+    .await;
+
+    let _ = fs::remove_file(&staging_path);
+
+    result.map(|_| manifest)
+}
+
+fn write_archive(output: &Path, manifest: &Manifest, data: &mut File) -> Result<(), BackupError> {
+    let file = File::create(output)
+        //
+        .map_err(FileError::on("create", output))?;
+
+    let encoder = zstd::Encoder::new(file, 0)
+        //
+        .map_err(BackupError::io("failed to start compressing"))?;
+
+    let mut builder = tar::Builder::new(encoder);
+
+    let manifest = serde_json::to_vec_pretty(manifest)
+        //
+        .map_err(BackupError::EncodeManifest)?;
+
+    append(&mut builder, MANIFEST_ENTRY, manifest.len() as u64, manifest.as_slice())?;
+
+    let data_length = data
+        //
+        .metadata()
+        //
+        .map_err(BackupError::io("failed to read the size of the staged data"))?
+        //
+        .len();
+
+    append(&mut builder, DATA_ENTRY, data_length, data)?;
+
+    builder
+        //
+        .into_inner()
+        //
+        .map_err(BackupError::io("failed to finish the archive"))?
+        //
+        .finish()
+        //
+        .map_err(BackupError::io("failed to finish compressing"))?;
+
+    Ok(())
+}
+
+fn append<W, R>(builder: &mut tar::Builder<W>, path: &str, length: u64, reader: R) -> Result<(), BackupError>
+where
+    //
+    W: std::io::Write,
+    //
+    R: std::io::Read,
+{
+    let mut header = tar::Header::new_gnu();
+
+    header.set_size(length);
+
+    header.set_mode(0o600);
+
+    header.set_mtime(OffsetDateTime::now_utc().unix_timestamp() as u64);
+
+    builder
+        //
+        .append_data(&mut header, path, reader)
+        //
+        .map_err(BackupError::io(format!("failed to add {path} to the archive")))
+}
+
+/// Reads the manifest of an archive and stages its data in an anonymous temporary file.
+///
+/// Archives in an unknown format, or taken from an app version this CLI cannot run, are refused.
+pub fn read(input: &Path) -> Result<(Manifest, File), BackupError> {
+    let file = File::open(input)
+        //
+        .map_err(FileError::on("open", input))?;
+
+    let decoder = zstd::Decoder::new(file)
+        //
+        .map_err(BackupError::io("failed to start decompressing"))?;
+
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut entries = archive
+        //
+        .entries()
+        //
+        .map_err(BackupError::io("failed to read the archive"))?;
+
+    let mut next_entry = |expected: &'static str| -> Result<tar::Entry<_>, BackupError> {
+        let entry = entries
+            //
+            .next()
+            //
+            .ok_or(BackupError::MissingEntry(expected))?
+            //
+            .map_err(BackupError::io("failed to read the archive"))?;
+
+        let path = entry
+            //
+            .path()
+            //
+            .map_err(BackupError::io("failed to read the name of an entry"))?
+            //
+            .to_string_lossy()
+            //
+            .to_string();
+
+        if path != expected {
+            return Err(BackupError::UnexpectedEntry { expected, found: path });
+        }
+
+        Ok(entry)
+    };
+
+    let manifest = serde_json::from_reader::<_, Manifest>(next_entry(MANIFEST_ENTRY)?)
+        //
+        .map_err(BackupError::ParseManifest)?;
+
+    if manifest.format > FORMAT_VERSION {
+        return Err(BackupError::UnknownFormat(manifest.format));
+    }
+
+    if !versioning::is_compatible_cli_version(&manifest.cli_version) {
+        return Err(BackupError::IncompatibleCli(manifest.cli_version));
+    }
+
+    if !versioning::is_compatible_app_version(&manifest.app_version) {
+        return Err(BackupError::IncompatibleApp(manifest.app_version));
+    }
+
+    let mut staging = staging_file()?;
+
+    io::copy(&mut next_entry(DATA_ENTRY)?, &mut staging)
+        //
+        .map_err(BackupError::io("failed to extract the data"))?;
+
+    staging
+        //
+        .seek(SeekFrom::Start(0))
+        //
+        .map_err(BackupError::io("failed to rewind the staged data"))?;
+
+    Ok((manifest, staging))
+}
+
+/// Creates an anonymous temporary file to stage data in.
+pub fn staging_file() -> Result<File, FileError> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let staging_path = env::temp_dir().join(format!(
+        "mayo-staging-{}-{}.tar",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let staging = File::options()
+        //
+        .read(true)
+        //
+        .write(true)
+        //
+        .create_new(true)
+        //
+        .open(&staging_path)
+        //
+        .map_err(FileError::on("create", &staging_path))?;
+
+    // the file stays usable through the handle, and disappears however the process ends
+    let _ = fs::remove_file(&staging_path);
+
+    Ok(staging)
+}
+
+/// Creates a server from restored parameters and data, and starts it.
+///
+/// An existing server with the same name, or leftover data where the new server's data would go, is only replaced
+/// when `force` is set. The archive is unpacked into a staging volume first, so that nothing is removed unless the
+/// data could be read and unpacked.
+pub async fn restore<E: Engine>(
+    mayo: &Mayo<E>,
+    parameters: Parameters,
+    origin: Option<&str>,
+    data: File,
+    force: bool,
+) -> Result<String> {
+    let name = parameters.name.clone();
+
+    let existing = mayo.find_server(name.as_str()).await?;
+
+    if existing.is_some() && !force {
+        return Err(ServerError::AlreadyExists(name.to_string()).into());
+    }
+
+    let volume = parameters.storage.source(&name);
+
+    match &parameters.storage {
+        Storage::Volume => {
+            if !force && mayo.volume_exists(&volume).await? {
+                return Err(ServerError::VolumeExists(volume).into());
+            }
+        }
+
+        Storage::Bind(path) => {
+            if !force && !mayo.is_data_empty(&parameters).await? {
+                return Err(ServerError::DirectoryNotEmpty(path.clone()).into());
+            }
+        }
+    }
+
+    let staging = format!("mayo-{name}-restore");
+
+    if mayo.volume_exists(&staging).await? {
+        // it may hold the only copy of the data of a restore that failed at the last step
+        if !force {
+            return Err(ServerError::VolumeExists(staging).into());
+        }
+
+        mayo.remove_volume(&staging).await?;
+    }
+
+    if let Err(error) = mayo.upload_data(&parameters.version, &staging, data).await {
+        if let Err(removal_error) = mayo.remove_volume(&staging).await {
+            mayo.warn(Warning::VolumeLeft {
+                volume: staging,
+
+                error: Box::new(removal_error),
+            });
+        }
+
+        return Err(error);
+    }
+
+    if let Some(existing) = existing {
+        mayo.remove_server(&existing, true)
+            //
+            .await?;
+    }
+
+    // leftover data, e.g. of a server removed without its data
+    if let Storage::Volume = parameters.storage
+        && mayo.volume_exists(&volume).await?
+    {
+        mayo.remove_volume(&volume)
+            //
+            .await?;
+    }
+
+    // the data is copied over what the directory holds, so stale files would survive the restore
+    if let Storage::Bind(_) = parameters.storage
+        && force
+    {
+        mayo.clear_data(&parameters)
+            //
+            .await?;
+    }
+
+    mayo
+        //
+        .copy_volume(&parameters, &staging)
+        //
+        .await
+        //
+        .map_err(|error| ServerError::Staged {
+            volume: staging.clone(),
+
+            source: Box::new(error),
+        })?;
+
+    mayo.remove_volume(&staging)
+        //
+        .await?;
+
+    mayo
+        //
+        .create_server(parameters, origin)
+        //
+        .await
+}
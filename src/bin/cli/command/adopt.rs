@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use anyhow::*;
+
+use comfy_table::presets;
+use comfy_table::*;
+
+use inquire::Confirm;
+
+use super::connect;
+
+use manage::adopt::Adoption;
+use manage::parameters::Name;
+use manage::parameters::Parameters;
+
+/// Recreates a container started by hand as a server, after showing what it maps to and what it does not.
+pub async fn adopt(
+    host: Option<String>,
+    container: String,
+    name: Option<String>,
+    yes: bool,
+    timeout: u64,
+) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let name = name
+        //
+        .map(Name::try_new)
+        //
+        .transpose()?;
+
+    let adoption = mayo
+        //
+        .plan_adoption(&container, name)
+        //
+        .await
+        //
+        .with_context(|| format!("failed to inspect {container}"))?;
+
+    print(&adoption);
+
+    if !yes {
+        let confirmed = Confirm::new(&format!("Would you like to adopt {}?", adoption.container_name))
+            //
+            .with_default(adoption.unmapped.is_empty())
+            //
+            .prompt_skippable()
+            //
+            .context("failed to inquire the confirmation")?;
+
+        if confirmed != Some(true) {
+            return Ok(());
+        }
+    }
+
+    let container_id = mayo
+        //
+        .adopt(&adoption, Duration::from_secs(timeout))
+        //
+        .await
+        //
+        .context("failed to adopt the container")?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
+
+fn print(adoption: &Adoption) {
+    let Adoption {
+        container_name,
+
+        parameters:
+            Parameters {
+                name,
+
+                version,
+
+                port,
+
+                authentication,
+
+                tls,
+
+                storage,
+
+                libraries,
+
+                environment,
+                ..
+            },
+
+        copy_from,
+
+        unmapped,
+        ..
+    } = adoption;
+
+    let mut table = Table::new();
+
+    table
+        //
+        .load_preset(presets::NOTHING)
+        //
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    let data = match copy_from {
+        Some(volume) => format!("{} ({storage}), copied from {volume}", storage.source(name)),
+
+        None => format!("{} ({storage})", storage.source(name)),
+    };
+
+    let rows = [
+        //
+        ("CONTAINER", container_name.to_string()),
+        //
+        ("NAME", name.to_string()),
+        //
+        ("VERSION", version.to_string()),
+        //
+        ("PORT", port.to_string()),
+        //
+        ("AUTHENTICATION", authentication.to_string()),
+        //
+        ("TLS", tls.to_string()),
+        //
+        ("DATA", data),
+        //
+        ("LIBRARIES", libraries.to_string()),
+        //
+        ("ENVIRONMENT", environment.to_string()),
+    ];
+
+    for (key, value) in rows {
+        table.add_row(vec![
+            //
+            Cell::new(key),
+            //
+            Cell::new(value),
+        ]);
+    }
+
+    println!("{table}");
+
+    if !unmapped.is_empty() {
+        println!();
+
+        println!("⚠️  The server will not have:");
+
+        for setting in unmapped {
+            println!("  - {setting}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::error;
+use manage::fleet;
+use manage::fleet::Change;
+use manage::fleet::Fleet;
+use manage::fleet::Plan;
+
+/// Makes the servers of a host match a manifest, or with `plan`, only prints what that would change.
+///
+/// Changes are made one at a time, and a failed one does not stop the others.
+pub async fn apply(host: Option<String>, file: PathBuf, prune: bool, plan: bool, timeout: u64) -> Result<()> {
+    // read before connecting, so that a typo in the manifest fails fast
+    let fleet = Fleet::load(&file)?;
+
+    let mayo = connect(host.as_deref()).await?;
+
+    let servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    let changes = fleet::plan(&fleet, servers, prune)
+        //
+        .await
+        //
+        .context("failed to plan the changes")?;
+
+    print(&changes);
+
+    if plan || changes.changes.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+
+    let mut failures = 0;
+
+    for change in changes.changes {
+        let name = change.name().clone();
+
+        match fleet::apply(&mayo, change, Duration::from_secs(timeout)).await {
+            Result::Ok(Some(container_id)) => println!("{name}: {container_id}"),
+
+            Result::Ok(None) => println!("{name}: removed"),
+
+            Err(error) => {
+                eprintln!("{name}: {}", error::describe(&error));
+
+                failures += 1;
+            }
+        }
+    }
+
+    ensure!(failures == 0, "{failures} change(s) could not be made");
+
+    Ok(())
+}
+
+fn print(plan: &Plan) {
+    let Plan {
+        changes,
+
+        unchanged,
+
+        read_only,
+
+        unlisted,
+    } = plan;
+
+    for change in changes {
+        match change {
+            Change::Remove(server) => {
+                println!("- {} (remove, its data is kept)", server.parameters.name);
+            }
+
+            Change::Replace { server, fields, .. } => {
+                println!("~ {}: {}", server.parameters.name, fields.join(", "));
+            }
+
+            Change::Create(parameters) => {
+                println!("+ {} (create, version {})", parameters.name, parameters.version);
+            }
+        }
+    }
+
+    for name in read_only {
+        eprintln!("⚠️  {name} was written by a newer CLI and is left alone");
+    }
+
+    for name in unlisted {
+        eprintln!("⚠️  {name} is not in the manifest and is kept, see `apply --prune`");
+    }
+
+    if changes.is_empty() {
+        println!("All {} server(s) match the manifest 🎉", unchanged.len());
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::fs::TryLockError;
+use std::path::PathBuf;
+
+use anyhow::*;
+
+use chrono::Local;
+use chrono::Utc;
+
+use super::connect;
+
+use manage::backup;
+use manage::config::Config;
+use manage::mayo::Mayo;
+use manage::schedule;
+use manage::schedule::Retention;
+use manage::schedule::Schedule;
+use manage::server::Server;
+
+pub async fn backup(host: Option<String>, name: String, output: PathBuf) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&name)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    backup::create(&mayo, &server, &output)
+        //
+        .await
+        //
+        .context("failed to back up the server")?;
+
+    println!("{}", output.display());
+
+    Ok(())
+}
+
+/// Sets the backup schedule of a server, replacing any previous one.
+pub async fn schedule(
+    host: Option<String>,
+    name: String,
+    cron: String,
+    directory: PathBuf,
+    retention: Retention,
+) -> Result<()> {
+    let schedule = Schedule::try_new(host.clone(), cron, directory, retention)?;
+
+    let mayo = connect(host.as_deref()).await?;
+
+    ensure!(
+        mayo
+            //
+            .find_server(&name)
+            //
+            .await
+            //
+            .context("failed to find the server")?
+            //
+            .is_some(),
+        "there is no server named {name}"
+    );
+
+    fs::create_dir_all(&schedule.directory)
+        //
+        .with_context(|| format!("failed to create {}", schedule.directory.display()))?;
+
+    let mut config = Config::load()?;
+
+    config.backups.insert(name, schedule);
+
+    config.save()?;
+
+    Ok(())
+}
+
+/// Removes the backup schedule of a server, leaving its archives in place.
+pub fn unschedule(name: String) -> Result<()> {
+    let mut config = Config::load()?;
+
+    ensure!(
+        config.backups.remove(&name).is_some(),
+        "there is no backup schedule for {name}"
+    );
+
+    config.save()?;
+
+    Ok(())
+}
+
+/// Takes the scheduled backups that are due and prunes old archives, meant to be run from cron every few minutes.
+pub async fn run_due() -> Result<()> {
+    // overlapping runs would back up the same servers twice and prune archives that are still being written
+    let Some(_lock) = lock()? else {
+        eprintln!("another run is in progress");
+
+        return Ok(());
+    };
+
+    let config = Config::load()?;
+
+    if config.backups.is_empty() {
+        return Ok(());
+    }
+
+    // each host is connected to once, however many of its servers are scheduled
+    let mut schedules_by_host = BTreeMap::<Option<&str>, Vec<(&String, &Schedule)>>::new();
+
+    for (name, schedule) in &config.backups {
+        schedules_by_host
+            //
+            .entry(schedule.host.as_deref())
+            //
+            .or_default()
+            //
+            .push((name, schedule));
+    }
+
+    let mut failures = 0;
+
+    for (host, schedules) in schedules_by_host {
+        let servers = async {
+            let mayo = connect(host).await?;
+
+            let servers = mayo
+                //
+                .list_servers()
+                //
+                .await
+                //
+                .context("failed to list servers")?;
+
+            Ok((mayo, servers))
+        }
+        .await;
+
+        let (mayo, servers) = match servers {
+            Result::Ok(result) => result,
+
+            Err(error) => {
+                for (name, _) in &schedules {
+                    eprintln!("{name}: {error:#}");
+                }
+
+                failures += schedules.len();
+
+                continue;
+            }
+        };
+
+        for (name, schedule) in schedules {
+            let server = servers
+                //
+                .iter()
+                //
+                .find(|server| server.parameters.name.as_str() == name);
+
+            let Some(server) = server else {
+                eprintln!("{name}: there is no server with this name");
+
+                failures += 1;
+
+                continue;
+            };
+
+            if let Err(error) = run_server(&mayo, server, schedule).await {
+                eprintln!("{name}: {error:#}");
+
+                failures += 1;
+            }
+        }
+    }
+
+    ensure!(failures == 0, "{failures} scheduled backup(s) failed");
+
+    Ok(())
+}
+
+async fn run_server(mayo: &Mayo, server: &Server, schedule: &Schedule) -> Result<()> {
+    let name = server.parameters.name.as_str();
+
+    schedule.remove_partial_archives(name)?;
+
+    let archives = schedule.archives(name)?;
+
+    if schedule.is_due(archives.first(), Local::now())? {
+        let output = schedule.archive_path(name, Utc::now());
+
+        let partial_output = schedule::partial_path(&output);
+
+        backup::create(mayo, server, &partial_output)
+            //
+            .await
+            //
+            .context("failed to back up the server")?;
+
+        fs::rename(&partial_output, &output)
+            //
+            .with_context(|| format!("failed to rename {}", partial_output.display()))?;
+
+        println!("{name}: {}", output.display());
+    }
+
+    for path in schedule.prune(name)? {
+        println!("{name}: removed {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Takes the lock of scheduled backups, or returns `None` when another process holds it.
+fn lock() -> Result<Option<File>> {
+    let data_dir = dirs::data_dir()
+        //
+        .context("failed to find the data directory")?;
+
+    let directory = data_dir.join("mayo");
+
+    fs::create_dir_all(&directory)
+        //
+        .with_context(|| format!("failed to create {}", directory.display()))?;
+
+    let path = directory.join("backup.lock");
+
+    let file = File::create(&path)
+        //
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    // the lock is released by the operating system when the file is closed, even if the process is killed
+    match file.try_lock() {
+        Result::Ok(()) => Ok(Some(file)),
+
+        Err(TryLockError::WouldBlock) => Ok(None),
+
+        Err(TryLockError::Error(error)) => Err(error).with_context(|| format!("failed to lock {}", path.display())),
+    }
+}
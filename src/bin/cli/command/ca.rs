@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::*;
+
+use manage::certificate::LocalCa;
+
+pub fn export(output: Option<PathBuf>) -> Result<()> {
+    let LocalCa {
+        //
+        certificate,
+        ..
+    } = LocalCa::load_or_create()
+        //
+        .context("failed to load the local CA")?;
+
+    if let Some(output) = output {
+        fs::write(&output, certificate)
+            //
+            .with_context(|| format!("failed to write {}", output.display()))?;
+    } else {
+        print!("{certificate}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::backup;
+use manage::mayo::Mayo;
+use manage::parameters::Name;
+use manage::parameters::Port;
+use manage::parameters::Storage;
+use manage::server::Server;
+
+/// Creates a copy of a server, with a new name and port, and with or without its data.
+///
+/// Copying the data stops the original server for as long as the copy takes, so that the copy is consistent.
+pub async fn clone(
+    host: Option<String>,
+    name: String,
+    new_name: Option<String>,
+    port: Option<u16>,
+    no_data: bool,
+    timeout: u64,
+) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    let Some(server) = servers
+        //
+        .iter()
+        //
+        .find(|server| server.parameters.name.as_str() == name)
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    let new_name = match new_name {
+        Some(new_name) => Name::try_new(new_name)?,
+
+        None => Name::generate(),
+    };
+
+    let port = match port {
+        Some(port) => {
+            if let Some(other) = servers.iter().find(|server| server.parameters.port.get() == port) {
+                bail!("the port {port} is already used by {}", other.parameters.name);
+            }
+
+            Port::new(port)
+        }
+
+        None => free_port(&mayo, &servers, server.parameters.port.get())?,
+    };
+
+    let mut parameters = server.parameters.clone();
+
+    parameters.name = new_name;
+
+    parameters.port = port;
+
+    // a clone sharing the original's directory would write to the original's data
+    if let Storage::Bind(path) = &parameters.storage {
+        eprintln!(
+            "⚠️  {name} keeps its data in {}, the clone keeps its data in a volume instead",
+            path.display()
+        );
+
+        parameters.storage = Storage::Volume;
+    }
+
+    // renewing the original's certificate from the clone would take the original's port and domains
+    if parameters.acme.take().is_some() {
+        eprintln!("⚠️  the clone keeps the current certificate, but does not renew it through ACME");
+    }
+
+    let container_id = if no_data {
+        ensure!(
+            !mayo.volume_exists(&parameters.storage.source(&parameters.name)).await?,
+            "the data of a server named {} already exists",
+            parameters.name
+        );
+
+        mayo
+            //
+            .create_server(parameters, Some(&name))
+            //
+            .await
+            //
+            .context("failed to create the server")?
+    } else {
+        let data = copy_data(&mayo, server).await?;
+
+        backup::restore(&mayo, parameters, Some(&name), data, false)
+            //
+            .await
+            //
+            .context("failed to create the server")?
+    };
+
+    mayo
+        //
+        .wait_until_ready(&container_id, Duration::from_secs(timeout))
+        //
+        .await?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
+
+async fn copy_data(mayo: &Mayo, server: &Server) -> Result<std::fs::File> {
+    let mut data = backup::staging_file()?;
+
+    let is_running = server.state == "running";
+
+    if is_running {
+        mayo.stop_server(server).await?;
+    }
+
+    let download_result = mayo.download_data(&server.id, &mut data).await;
+
+    if is_running {
+        mayo.start_server(server).await?;
+    }
+
+    download_result?;
+
+    data
+        //
+        .seek(SeekFrom::Start(0))
+        //
+        .context("failed to rewind the copied data")?;
+
+    Ok(data)
+}
+
+/// The first port after `after` that no server uses and, on a local daemon, that nothing else listens on.
+fn free_port(mayo: &Mayo, servers: &[Server], after: u16) -> Result<Port> {
+    (after.saturating_add(1)..=u16::MAX)
+        //
+        .find(|&port| {
+            let is_taken = servers
+                //
+                .iter()
+                //
+                .any(|server| server.parameters.port.get() == port);
+
+            // servers use host networking, so a port is free when it can be bound
+            !is_taken && (!mayo.is_local() || TcpListener::bind(("0.0.0.0", port)).is_ok())
+        })
+        //
+        .map(Port::new)
+        //
+        .with_context(|| format!("there is no free port after {after}"))
+}
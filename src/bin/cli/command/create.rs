@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+use super::warn;
+
+use manage::parameters::Environment;
+use manage::parameters::Parameters;
+
+pub async fn create(
+    host: Option<String>,
+    timeout: u64,
+    env: Vec<String>,
+    env_files: Vec<PathBuf>,
+    secrets: Vec<String>,
+) -> Result<()> {
+    // validated before any prompt, so that a typo does not cost a whole interview
+    let environment = Environment::try_new(&env, &env_files, &secrets)
+        //
+        .context("failed to read the environment")?;
+
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(mut parameters) = Parameters::inquire(mayo.is_local(), &warn).await? else {
+        return Ok(());
+    };
+
+    parameters.environment = environment;
+
+    let container_id = mayo
+        //
+        .create_server(parameters, None)
+        //
+        .await
+        //
+        .context("failed to create a server")?;
+
+    mayo
+        //
+        .wait_until_ready(&container_id, Duration::from_secs(timeout))
+        //
+        .await?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
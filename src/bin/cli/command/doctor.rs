@@ -0,0 +1,82 @@
+use anyhow::*;
+
+use comfy_table::presets;
+use comfy_table::*;
+
+use super::connect;
+
+use manage::engine::EngineInfo;
+
+/// Shows which engine serves a host and what to be aware of when running servers on it.
+pub async fn doctor(host: Option<String>) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let engine = mayo.engine_info().await?;
+
+    let notes = engine.notes();
+
+    let EngineInfo {
+        kind,
+
+        version,
+
+        api_version,
+
+        platform,
+
+        rootless,
+    } = engine;
+
+    let servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    let mut table = Table::new();
+
+    table
+        //
+        .load_preset(presets::NOTHING)
+        //
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    let rows = [
+        //
+        ("HOST", mayo.host().to_string()),
+        //
+        ("ENGINE", format!("{kind} {version}")),
+        //
+        ("API VERSION", api_version),
+        //
+        ("PLATFORM", platform),
+        //
+        ("ROOTLESS", if rootless { "yes" } else { "no" }.to_string()),
+        //
+        ("SERVERS", servers.len().to_string()),
+        //
+        (
+            "NOTES",
+            if notes.is_empty() {
+                "none".to_string()
+            } else {
+                notes.join("\n")
+            },
+        ),
+    ];
+
+    for (key, value) in rows {
+        table.add_row(vec![
+            //
+            Cell::new(key),
+            //
+            Cell::new(value),
+        ]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
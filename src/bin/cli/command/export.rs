@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::certificate;
+use manage::cli::ExportFormat;
+use manage::compose::Export;
+
+/// Writes a server as a compose file, with its secrets in an env file next to it that only the current user can read.
+pub async fn export(host: Option<String>, name: String, format: ExportFormat, output: PathBuf) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&name)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    let Export { compose, env_file } = match format {
+        ExportFormat::Compose => mayo
+            //
+            .export_compose(&server)
+            //
+            .await
+            //
+            .context("failed to export the server")?,
+    };
+
+    let compose_path = output.join("docker-compose.yml");
+
+    ensure!(!compose_path.exists(), "{} already exists", compose_path.display());
+
+    fs::create_dir_all(&output)
+        //
+        .with_context(|| format!("failed to create {}", output.display()))?;
+
+    if let Some((file_name, contents)) = env_file {
+        let env_file_path = output.join(file_name);
+
+        certificate::write_private(&env_file_path, &contents)?;
+
+        println!("{}", env_file_path.display());
+    }
+
+    fs::write(&compose_path, compose)
+        //
+        .with_context(|| format!("failed to write {}", compose_path.display()))?;
+
+    println!("{}", compose_path.display());
+
+    Ok(())
+}
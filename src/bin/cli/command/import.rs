@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::compose;
+use manage::compose::Import;
+
+/// Creates the server described by a compose file, taking its secrets from the service's env files.
+///
+/// A service of the app that was not exported by the CLI is mapped like an adopted container.
+pub async fn import(host: Option<String>, file: PathBuf, service: Option<String>, timeout: u64) -> Result<()> {
+    let Import {
+        parameters,
+
+        origin,
+
+        missing_secrets,
+
+        unmapped,
+    } = compose::import(&file, service.as_deref())?;
+
+    for key in missing_secrets {
+        eprintln!("⚠️  the secret {key} has no value in the env files of the service, so it is left out");
+    }
+
+    for setting in unmapped {
+        eprintln!("⚠️  the server will not have {setting}");
+    }
+
+    let mayo = connect(host.as_deref()).await?;
+
+    let container_id = mayo
+        //
+        .create_server(parameters, origin.as_deref())
+        //
+        .await
+        //
+        .context("failed to create the server")?;
+
+    mayo
+        //
+        .wait_until_ready(&container_id, Duration::from_secs(timeout))
+        //
+        .await?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use anyhow::*;
+
+use comfy_table::presets;
+use comfy_table::*;
+
+use super::connect;
+
+use manage::config::Config;
+use manage::mayo::Mayo;
+use manage::parameters::Parameters;
+use manage::server::Server;
+
+pub async fn inspect(host: Option<String>, name: String) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&name)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    let lineage = lineage(&mayo, &server).await?;
+
+    let config = Config::load()?;
+
+    let backups = config
+        //
+        .backups
+        //
+        .get(name.as_str())
+        //
+        .map(|schedule| schedule.to_string())
+        //
+        .unwrap_or_else(|| "-".to_string());
+
+    let is_read_only = server.is_read_only();
+
+    let Server {
+        id,
+
+        state,
+
+        health,
+
+        schema,
+
+        origin: _,
+
+        parameters:
+            Parameters {
+                name,
+
+                version,
+
+                port,
+
+                authentication,
+
+                tls,
+
+                security,
+
+                storage,
+
+                libraries,
+
+                environment,
+
+                acme,
+            },
+    } = server;
+
+    let mut table = Table::new();
+
+    table
+        //
+        .load_preset(presets::NOTHING)
+        //
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    let rows = [
+        //
+        ("NAME", name.to_string()),
+        //
+        ("ID", id),
+        //
+        ("ORIGIN", lineage),
+        //
+        ("VERSION", version.to_string()),
+        //
+        ("STATE", state),
+        //
+        ("HEALTH", health.unwrap_or_else(|| "-".to_string())),
+        //
+        ("PORT", port.to_string()),
+        //
+        ("AUTHENTICATION", authentication.to_string()),
+        //
+        ("TLS", tls.to_string()),
+        //
+        (
+            "ACME",
+            acme
+                //
+                .map(|acme| {
+                    format!(
+                        "{} via {} ({})",
+                        acme.domains.join(", "),
+                        acme.directory,
+                        acme.challenge
+                    )
+                })
+                //
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        //
+        ("SECURITY", security.to_string()),
+        //
+        ("DATA", format!("{} ({storage})", storage.source(&name))),
+        //
+        ("LIBRARIES", libraries.to_string()),
+        //
+        ("ENVIRONMENT", environment.to_string()),
+        //
+        ("BACKUPS", backups),
+        //
+        (
+            "SCHEMA",
+            if is_read_only {
+                format!("{schema} (read-only, written by a newer CLI)")
+            } else {
+                schema.to_string()
+            },
+        ),
+    ];
+
+    for (key, value) in rows {
+        table.add_row(vec![
+            //
+            Cell::new(key),
+            //
+            Cell::new(value),
+        ]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
+
+/// The servers a server was cloned from, nearest first, e.g. `staging ← production`.
+///
+/// The chain stops at the first origin that no longer exists, which is still shown.
+async fn lineage(mayo: &Mayo, server: &Server) -> Result<String> {
+    let Some(origin) = &server.origin else {
+        return Ok("-".to_string());
+    };
+
+    let servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    let mut result = vec![origin.clone()];
+
+    while let Some(origin) = servers
+        //
+        .iter()
+        //
+        .find(|server| Some(server.parameters.name.as_str()) == result.last().map(String::as_str))
+        //
+        .and_then(|server| server.origin.clone())
+    {
+        // a server can be cloned from an earlier server of the same name
+        if result.contains(&origin) {
+            break;
+        }
+
+        result.push(origin);
+    }
+
+    Ok(result.join(" ← "))
+}
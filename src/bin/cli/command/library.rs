@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::parameters::Library;
+
+pub async fn add(host: Option<String>, server: String, name: String, path: PathBuf, timeout: u64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&server)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {server}");
+    };
+
+    // the paths of a remote daemon are not on this machine, so it is up to the daemon to complain about them
+    let library = if mayo.is_local() {
+        let path = path
+            //
+            .canonicalize()
+            //
+            .with_context(|| format!("failed to resolve {}", path.display()))?;
+
+        let library = Library::try_new(name, path)?;
+
+        let engine = mayo.engine_info().await?;
+
+        library
+            //
+            .check(server.parameters.security, engine.rootless)
+            //
+            .context("the library cannot be used")?;
+
+        library
+    } else {
+        Library::try_new(name, path)?
+    };
+
+    let mut parameters = server.parameters.clone();
+
+    parameters.libraries.add(library)?;
+
+    let container_id = mayo
+        //
+        .replace_server(&server, parameters, Duration::from_secs(timeout))
+        //
+        .await
+        //
+        .context("failed to replace the server")?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
+
+pub async fn rm(host: Option<String>, server: String, name: String, timeout: u64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&server)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {server}");
+    };
+
+    let mut parameters = server.parameters.clone();
+
+    parameters.libraries.remove(&name)?;
+
+    let container_id = mayo
+        //
+        .replace_server(&server, parameters, Duration::from_secs(timeout))
+        //
+        .await
+        //
+        .context("failed to replace the server")?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use anyhow::*;
+
+use comfy_table::presets;
+use comfy_table::*;
+
+use futures_util::future;
+
+use super::connect;
+
+use manage::certificate;
+use manage::config::Config;
+use manage::parameters::Parameters;
+use manage::parameters::Tls;
+use manage::server::HiddenContainer;
+use manage::server::Server;
+
+/// Lists the servers of one host, or of several with a host column.
+///
+/// Several hosts are queried concurrently, each within `host_timeout` seconds, and a host that cannot be reached is
+/// shown as such rather than failing the whole listing. With `all`, containers this CLI cannot manage are listed too,
+/// with the reason, and make the command fail so that they do not go unnoticed.
+pub async fn ls(hosts: Vec<String>, all: bool, all_hosts: bool, host_timeout: u64, warn_days: i64) -> Result<()> {
+    let mut hosts = hosts;
+
+    if all_hosts {
+        let config = Config::load()?;
+
+        ensure!(
+            !config.hosts.is_empty(),
+            "there are no hosts in {}",
+            Config::path()?.display()
+        );
+
+        let others = config
+            //
+            .hosts
+            //
+            .into_keys()
+            //
+            .filter(|name| !hosts.contains(name))
+            //
+            .collect::<Vec<_>>();
+
+        hosts.extend(others);
+    }
+
+    let show_host = hosts.len() > 1;
+
+    if !show_host {
+        let mayo = connect(hosts.first().map(String::as_str)).await?;
+
+        let (servers, hidden) = mayo
+            //
+            .list_all_servers()
+            //
+            .await
+            //
+            .context("failed to list servers")?;
+
+        return print(
+            Listing {
+                servers: servers.into_iter().map(|server| (String::new(), server)).collect(),
+
+                hidden: hidden.into_iter().map(|container| (String::new(), container)).collect(),
+
+                unreachable: Vec::new(),
+            },
+            false,
+            all,
+            warn_days,
+        );
+    }
+
+    let timeout = Duration::from_secs(host_timeout);
+
+    let results = future::join_all(hosts.iter().map(|host| async move {
+        let servers = tokio::time::timeout(timeout, async {
+            let mayo = connect(Some(host)).await?;
+
+            mayo
+                //
+                .list_all_servers()
+                //
+                .await
+                //
+                .context("failed to list servers")
+        })
+        //
+        .await
+        //
+        .unwrap_or_else(|_| Err(anyhow!("no answer within {host_timeout} seconds")));
+
+        (host, servers)
+    }))
+    //
+    .await;
+
+    let mut listing = Listing::default();
+
+    for (host, result) in results {
+        match result {
+            Result::Ok((servers, hidden)) => {
+                listing
+                    //
+                    .servers
+                    //
+                    .extend(servers.into_iter().map(|server| (host.clone(), server)));
+
+                listing
+                    //
+                    .hidden
+                    //
+                    .extend(hidden.into_iter().map(|container| (host.clone(), container)));
+            }
+
+            Err(error) => {
+                eprintln!("⚠️  {host}: {error:#}");
+
+                listing.unreachable.push(host.clone());
+            }
+        }
+    }
+
+    print(listing, true, all, warn_days)
+}
+
+/// What the hosts returned, each entry with the host it is on.
+#[derive(Default)]
+struct Listing {
+    servers: Vec<(String, Server)>,
+
+    /// Containers with server labels that this CLI cannot manage.
+    hidden: Vec<(String, HiddenContainer)>,
+
+    unreachable: Vec<String>,
+}
+
+fn print(listing: Listing, show_host: bool, all: bool, warn_days: i64) -> Result<()> {
+    let Listing {
+        //
+        mut servers,
+        //
+        mut hidden,
+        //
+        unreachable,
+    } = listing;
+
+    let hidden_count = hidden.len();
+
+    if !all {
+        hidden.clear();
+    }
+
+    if servers.is_empty() && hidden.is_empty() && unreachable.is_empty() {
+        println!("No servers 😔");
+    } else {
+        let mut table = Table::new();
+
+        let mut header = vec![
+            //
+            "NAME",
+            //
+            "VERSION",
+            //
+            "STATE",
+            //
+            "HEALTH",
+            //
+            "PORT",
+            //
+            "AUTHENTICATION",
+            //
+            "TLS",
+        ];
+
+        if show_host {
+            header.insert(0, "HOST");
+        }
+
+        if all {
+            header.push("REASON");
+        }
+
+        table
+            //
+            .load_preset(presets::NOTHING)
+            //
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            //
+            .set_header(header);
+
+        servers.sort_unstable_by(|(a_host, a), (b_host, b)| a_host.cmp(b_host).then_with(|| a.cmp(b)));
+
+        for (host, server) in servers {
+            let is_read_only = server.is_read_only();
+
+            let Server {
+                state,
+
+                health,
+
+                parameters:
+                    Parameters {
+                        name,
+
+                        version,
+
+                        port,
+
+                        authentication,
+
+                        tls,
+                        ..
+                    },
+                ..
+            } = server;
+
+            let name_cell = if is_read_only {
+                Cell::new(format!("{name} (read-only)")).fg(Color::Yellow)
+            } else {
+                Cell::new(name)
+            };
+
+            let mut row = vec![
+                //
+                name_cell,
+                //
+                Cell::new(version),
+                //
+                Cell::new(state),
+                //
+                Cell::new(health.unwrap_or_else(|| "-".to_string())),
+                //
+                Cell::new(port),
+                //
+                Cell::new(authentication),
+                //
+                tls_cell(&tls, warn_days),
+            ];
+
+            if show_host {
+                row.insert(0, Cell::new(host));
+            }
+
+            if all {
+                row.push(Cell::new("-"));
+            }
+
+            table.add_row(row);
+        }
+
+        hidden.sort_unstable_by(|(a_host, a), (b_host, b)| a_host.cmp(b_host).then_with(|| a.name.cmp(&b.name)));
+
+        for (host, container) in hidden {
+            let HiddenContainer {
+                //
+                name,
+                //
+                state,
+                //
+                reason,
+                ..
+            } = container;
+
+            let mut row = vec![Cell::new(name), Cell::new("-"), Cell::new(state)];
+
+            row.extend((0..4).map(|_| Cell::new("-")));
+
+            if show_host {
+                row.insert(0, Cell::new(host));
+            }
+
+            row.push(Cell::new(reason).fg(Color::Yellow));
+
+            table.add_row(row);
+        }
+
+        // the details were printed as warnings while listing
+        for host in unreachable {
+            let mut row = vec![Cell::new("-"), Cell::new("-"), Cell::new("unreachable").fg(Color::Red)];
+
+            row.extend((0..4).map(|_| Cell::new("-")));
+
+            row.insert(0, Cell::new(host));
+
+            if all {
+                row.push(Cell::new("-"));
+            }
+
+            table.add_row(row);
+        }
+
+        println!("{table}");
+    }
+
+    if all {
+        ensure!(
+            hidden_count == 0,
+            "{hidden_count} container(s) cannot be managed by this CLI"
+        );
+    } else if hidden_count > 0 {
+        eprintln!("⚠️  {hidden_count} container(s) cannot be managed by this CLI, see `ls --all`");
+    }
+
+    Ok(())
+}
+
+/// Shows how long the certificate is still valid, colored once it expires within `warn_days`.
+fn tls_cell(tls: &Tls, warn_days: i64) -> Cell {
+    let Some(crt) = tls.certificate() else {
+        return Cell::new(tls);
+    };
+
+    match certificate::days_until_expiry(crt) {
+        Result::Ok(days) if days < 0 => Cell::new("expired").fg(Color::Red),
+
+        Result::Ok(days) if days < warn_days => Cell::new(format!("{days} days left")).fg(Color::Yellow),
+
+        Result::Ok(days) => Cell::new(format!("{days} days left")),
+
+        Err(_) => Cell::new("unreadable").fg(Color::Red),
+    }
+}
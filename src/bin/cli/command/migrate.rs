@@ -0,0 +1,128 @@
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::backup;
+use manage::mayo::Mayo;
+use manage::server::Server;
+
+/// Moves a server to the Docker daemon at `to`, with its data, parameters and app version.
+///
+/// The source server is stopped for the whole move, so that no data written during it is lost, and started again if
+/// the server does not become healthy on the target. It is removed afterwards only when `remove_source` is set;
+/// otherwise it stays stopped as a fallback.
+pub async fn migrate(host: Option<String>, name: String, to: String, remove_source: bool, timeout: u64) -> Result<()> {
+    let source = connect(host.as_deref()).await?;
+
+    let target = connect(Some(&to)).await?;
+
+    let Some(server) = source
+        //
+        .find_server(&name)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    ensure!(
+        target
+            //
+            .find_server(&name)
+            //
+            .await
+            //
+            .context("failed to look for the server on the target")?
+            //
+            .is_none(),
+        "there already is a server named {name} on {to}"
+    );
+
+    // done while the server still runs, since it can take a while
+    target
+        //
+        .transfer_image(&server.parameters.version, &source)
+        //
+        .await
+        //
+        .context("failed to transfer the image")?;
+
+    let is_running = server.state == "running";
+
+    if is_running {
+        source.stop_server(&server).await?;
+    }
+
+    let result = move_server(&source, &target, &server, Duration::from_secs(timeout)).await;
+
+    let container_id = match result {
+        Result::Ok(container_id) => container_id,
+
+        Err(error) => {
+            if let Result::Ok(Some(moved)) = target.find_server(&name).await {
+                let _ = target.remove_server(&moved, true).await;
+            }
+
+            if is_running {
+                source
+                    //
+                    .start_server(&server)
+                    //
+                    .await
+                    //
+                    .context("failed to start the source server again")?;
+            }
+
+            return Err(error.context(format!("failed to move the server to {to}")));
+        }
+    };
+
+    if remove_source {
+        source
+            //
+            .remove_server(&server, true)
+            //
+            .await
+            //
+            .context("failed to remove the source server")?;
+    } else {
+        eprintln!("the source server is stopped and keeps its data");
+    }
+
+    println!("{container_id}");
+
+    Ok(())
+}
+
+async fn move_server(source: &Mayo, target: &Mayo, server: &Server, timeout: Duration) -> Result<String> {
+    let mut data = backup::staging_file()?;
+
+    source
+        //
+        .download_data(&server.id, &mut data)
+        //
+        .await?;
+
+    data
+        //
+        .seek(SeekFrom::Start(0))
+        //
+        .context("failed to rewind the staged data")?;
+
+    let container_id =
+        backup::restore(target, server.parameters.clone(), server.origin.as_deref(), data, false).await?;
+
+    target
+        //
+        .wait_until_ready(&container_id, timeout)
+        //
+        .await?;
+
+    Ok(container_id)
+}
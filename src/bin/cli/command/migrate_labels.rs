@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::labels::CURRENT_SCHEMA;
+
+/// Recreates the container of every server whose parameters label is in an older schema, so that it is written in the
+/// current one.
+///
+/// Servers are replaced one at a time, and a server that was not running is stopped again afterwards. Servers of a
+/// newer schema are left alone.
+pub async fn migrate_labels(host: Option<String>, timeout: u64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    let mut migrated = 0;
+
+    let mut failures = 0;
+
+    for server in servers {
+        let name = server.parameters.name.clone();
+
+        if server.schema >= CURRENT_SCHEMA {
+            continue;
+        }
+
+        let is_running = server.state == "running";
+
+        let result = async {
+            mayo
+                //
+                .replace_server(&server, server.parameters.clone(), Duration::from_secs(timeout))
+                //
+                .await
+                //
+                .context("failed to replace the server")?;
+
+            if !is_running && let Some(server) = mayo.find_server(name.as_str()).await? {
+                mayo.stop_server(&server).await?;
+            }
+
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Result::Ok(()) => {
+                println!("{name}: migrated from schema {} to {CURRENT_SCHEMA}", server.schema);
+
+                migrated += 1;
+            }
+
+            Err(error) => {
+                eprintln!("{name}: {error:#}");
+
+                failures += 1;
+            }
+        }
+    }
+
+    if migrated == 0 && failures == 0 {
+        println!("All labels are up to date 🎉");
+    }
+
+    ensure!(failures == 0, "{failures} server(s) could not be migrated");
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+pub mod adopt;
+pub mod apply;
+pub mod backup;
+pub mod ca;
+pub mod clone;
+pub mod create;
+pub mod doctor;
+pub mod export;
+pub mod import;
+pub mod inspect;
+pub mod library;
+pub mod ls;
+pub mod migrate;
+pub mod migrate_labels;
+pub mod restore;
+pub mod tls;
+
+use manage::error::Warning;
+use manage::mayo::Mayo;
+
+/// Connects to the daemon chosen with `--host`, printing the warnings of the operations that follow.
+pub async fn connect(host: Option<&str>) -> manage::error::Result<Mayo> {
+    Ok(Mayo::try_new(host).await?.on_warning(warn))
+}
+
+/// Prints what went wrong without failing a command.
+pub fn warn(warning: Warning) {
+    eprintln!("⚠️  {warning}");
+}
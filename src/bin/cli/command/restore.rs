@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+
+use manage::backup;
+use manage::certificate;
+use manage::parameters::Environment;
+use manage::parameters::Name;
+use manage::parameters::Storage;
+use manage::parameters::Tls;
+
+/// Creates a server from a backup archive, with the parameters and app version stored in it.
+///
+/// Backups do not contain secrets, so the TLS private key and the values of secret environment variables have to be
+/// given again; whatever is not given is left out, with a warning.
+pub async fn restore(
+    host: Option<String>,
+    archive: PathBuf,
+    name: Option<String>,
+    force: bool,
+    tls_key: Option<PathBuf>,
+    env: Vec<String>,
+    timeout: u64,
+) -> Result<()> {
+    let secret_values = Environment::try_new(&env, &[], &[])
+        //
+        .context("failed to read the environment")?;
+
+    let (manifest, data) = backup::read(&archive)
+        //
+        .with_context(|| format!("failed to read {}", archive.display()))?;
+
+    let mut parameters = manifest.parameters;
+
+    if let Some(name) = name {
+        let name = Name::try_new(name)?;
+
+        // a copy restored next to the original would otherwise unpack into, and then share, the original's directory
+        if name != parameters.name
+            && let Storage::Bind(path) = &parameters.storage
+        {
+            eprintln!(
+                "⚠️  {} keeps its data in {}, {name} keeps its data in a volume instead",
+                parameters.name,
+                path.display()
+            );
+
+            parameters.storage = Storage::Volume;
+        }
+
+        parameters.name = name;
+    }
+
+    if let Some(crt) = parameters.tls.certificate() {
+        match &tls_key {
+            Some(path) => {
+                let key = fs::read_to_string(path)
+                    //
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+
+                certificate::check_pair(crt, &key)
+                    //
+                    .context("the key does not belong to the archived certificate")?;
+
+                parameters.tls = Tls::new(crt.to_string(), key);
+            }
+
+            None => {
+                eprintln!(
+                    "⚠️  the archive has no TLS private key and none was given with --tls-key, so TLS is disabled"
+                );
+
+                parameters.tls = Tls::disabled();
+            }
+        }
+    }
+
+    for key in parameters.environment.fill_secret_values(&secret_values) {
+        eprintln!("⚠️  the secret {key} has no value in the archive and none was given with --env, so it is left out");
+    }
+
+    let mayo = connect(host.as_deref()).await?;
+
+    let container_id = backup::restore(&mayo, parameters, None, data, force)
+        //
+        .await
+        //
+        .context("failed to restore the server")?;
+
+    mayo
+        //
+        .wait_until_ready(&container_id, Duration::from_secs(timeout))
+        //
+        .await?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
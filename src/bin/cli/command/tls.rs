@@ -0,0 +1,256 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::*;
+
+use super::connect;
+use super::warn;
+
+use manage::acme;
+use manage::acme::Challenge;
+use manage::certificate;
+use manage::error;
+use manage::mayo::Mayo;
+use manage::parameters::Tls;
+use manage::server::Server;
+
+use time::OffsetDateTime;
+
+/// Renews every ACME certificate that expires within `days`, meant to be run from cron.
+pub async fn renew(host: Option<String>, days: i64, timeout: u64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let mut servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    servers.sort_unstable();
+
+    let mut failures = 0;
+
+    for server in servers {
+        let name = server.parameters.name.clone();
+
+        if server.parameters.acme.is_none() {
+            continue;
+        }
+
+        match renew_server(&mayo, server, days, timeout).await {
+            Result::Ok(Some(not_after)) => {
+                println!("{name}: renewed, valid until {}", not_after.date());
+            }
+
+            Result::Ok(None) => {
+                println!("{name}: not due for renewal");
+            }
+
+            Err(error) => {
+                eprintln!("{name}: {error:#}");
+
+                failures += 1;
+            }
+        }
+    }
+
+    ensure!(failures == 0, "failed to renew {failures} certificate(s)");
+
+    Ok(())
+}
+
+async fn renew_server(mayo: &Mayo, server: Server, days: i64, timeout: u64) -> Result<Option<OffsetDateTime>> {
+    let Some(acme) = &server.parameters.acme else {
+        return Ok(None);
+    };
+
+    let is_due = match server.parameters.tls.certificate() {
+        Some(crt) => {
+            let not_after = certificate::not_after(crt)
+                //
+                .context("failed to read the current certificate")?;
+
+            not_after - OffsetDateTime::now_utc() < time::Duration::days(days)
+        }
+
+        None => true,
+    };
+
+    if !is_due {
+        return Ok(None);
+    }
+
+    // the CLI answers HTTP-01 challenges on port 80, so a server listening there has to make room
+    let is_http_01 = matches!(acme.challenge, Challenge::Http01);
+
+    // checked before stopping anything, since the challenge would be answered on the wrong machine
+    ensure!(
+        mayo.is_local() || !is_http_01,
+        "HTTP-01 challenges are answered on this machine, not on {}; use DNS-01 for remote servers",
+        mayo.host()
+    );
+
+    let makes_room = is_http_01 && server.parameters.port.get() == acme::HTTP_01_PORT;
+
+    if makes_room {
+        mayo.stop_server(&server).await?;
+    }
+
+    let (crt, key) = match acme::obtain(acme, &warn).await {
+        Result::Ok(pair) => pair,
+
+        Err(error) => {
+            // the server is back up either way, and why the certificate could not be obtained matters more
+            if makes_room && let Err(restart_error) = mayo.start_server(&server).await {
+                eprintln!(
+                    "⚠️  failed to restart {} ({}), start it by hand",
+                    server.parameters.name,
+                    error::describe(&restart_error)
+                );
+            }
+
+            return Err(error).context("failed to obtain a certificate");
+        }
+    };
+
+    let not_after = certificate::not_after(&crt)
+        //
+        .context("failed to read the new certificate")?;
+
+    let mut parameters = server.parameters.clone();
+
+    parameters.tls = Tls::new(crt, key);
+
+    mayo
+        //
+        .replace_server(&server, parameters, Duration::from_secs(timeout))
+        //
+        .await
+        //
+        .context("failed to install the certificate")?;
+
+    Ok(Some(not_after))
+}
+
+/// Swaps a certificate and private key into a server.
+pub async fn set(
+    host: Option<String>,
+    name: String,
+    cert: PathBuf,
+    key: PathBuf,
+    hostnames: Vec<String>,
+    timeout: u64,
+) -> Result<()> {
+    let crt = fs::read_to_string(&cert)
+        //
+        .with_context(|| format!("failed to read {}", cert.display()))?;
+
+    let key = fs::read_to_string(&key)
+        //
+        .with_context(|| format!("failed to read {}", key.display()))?;
+
+    let warnings = certificate::check(&crt, &key, &hostnames)
+        //
+        .context("the certificate cannot be used")?;
+
+    for warning in warnings {
+        eprintln!("⚠️  {warning}");
+    }
+
+    // a certificate set by hand is no longer the ACME one, and must not be overwritten by `tls renew`
+    replace_tls(host, name, Tls::new(crt, key), timeout).await
+}
+
+/// Turns TLS off for a server.
+pub async fn rm(host: Option<String>, name: String, timeout: u64) -> Result<()> {
+    replace_tls(host, name, Tls::disabled(), timeout).await
+}
+
+async fn replace_tls(host: Option<String>, name: String, tls: Tls, timeout: u64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let Some(server) = mayo
+        //
+        .find_server(&name)
+        //
+        .await
+        //
+        .context("failed to find the server")?
+    else {
+        bail!("there is no server named {name}");
+    };
+
+    let mut parameters = server.parameters.clone();
+
+    parameters.tls = tls;
+
+    parameters.acme = None;
+
+    let container_id = mayo
+        //
+        .replace_server(&server, parameters, Duration::from_secs(timeout))
+        //
+        .await
+        //
+        .context("failed to replace the server")?;
+
+    println!("{container_id}");
+
+    Ok(())
+}
+
+/// Fails when any server's certificate expires within `days`, meant for monitoring.
+pub async fn check(host: Option<String>, days: i64) -> Result<()> {
+    let mayo = connect(host.as_deref()).await?;
+
+    let mut servers = mayo
+        //
+        .list_servers()
+        //
+        .await
+        //
+        .context("failed to list servers")?;
+
+    servers.sort_unstable();
+
+    let mut expiring = 0;
+
+    for server in servers {
+        let name = &server.parameters.name;
+
+        let Some(crt) = server.parameters.tls.certificate() else {
+            continue;
+        };
+
+        match certificate::not_after(crt) {
+            Result::Ok(not_after) => {
+                let days_left = certificate::days_until(not_after);
+
+                if days_left < 0 {
+                    println!("{name}: expired on {}", not_after.date());
+
+                    expiring += 1;
+                } else if days_left < days {
+                    println!("{name}: expires in {days_left} days, on {}", not_after.date());
+
+                    expiring += 1;
+                } else {
+                    println!("{name}: valid until {}", not_after.date());
+                }
+            }
+
+            Err(error) => {
+                println!("{name}: {}", error::describe(&error));
+
+                expiring += 1;
+            }
+        }
+    }
+
+    ensure!(expiring == 0, "{expiring} certificate(s) expire within {days} days");
+
+    Ok(())
+}
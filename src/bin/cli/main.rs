@@ -0,0 +1,186 @@
+mod command;
+
+use command::*;
+
+use anyhow::*;
+
+use clap::Parser;
+
+use manage::cli::BackupCommand;
+use manage::cli::CaCommand;
+use manage::cli::Cli;
+use manage::cli::Command;
+use manage::cli::LibraryCommand;
+use manage::cli::TlsCommand;
+use manage::schedule::Retention;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let Cli { hosts, command } = Cli::parse();
+
+    // only ls queries several hosts at once
+    if !matches!(command, Command::Ls { .. }) {
+        ensure!(hosts.len() <= 1, "--host can only be given once for this command");
+    }
+
+    let host = hosts.first().cloned();
+
+    match command {
+        Command::Ls {
+            all,
+
+            all_hosts,
+
+            host_timeout,
+
+            warn_days,
+        } => ls::ls(hosts, all, all_hosts, host_timeout, warn_days).await,
+
+        Command::Adopt {
+            container,
+
+            name,
+
+            yes,
+
+            timeout,
+        } => adopt::adopt(host, container, name, yes, timeout).await,
+
+        Command::Apply {
+            file,
+
+            prune,
+
+            plan,
+
+            timeout,
+        } => apply::apply(host, file, prune, plan, timeout).await,
+
+        Command::Backup { command, name, output } => match command {
+            Some(BackupCommand::Schedule {
+                name,
+
+                cron,
+
+                directory,
+
+                keep_last,
+
+                keep_daily,
+
+                keep_weekly,
+            }) => {
+                let retention = Retention {
+                    keep_last,
+
+                    keep_daily,
+
+                    keep_weekly,
+                };
+
+                backup::schedule(host, name, cron, directory, retention).await
+            }
+
+            Some(BackupCommand::Unschedule { name }) => backup::unschedule(name),
+
+            Some(BackupCommand::RunDue) => backup::run_due().await,
+
+            // both are required by clap when there is no subcommand
+            None => backup::backup(host, name.unwrap(), output.unwrap()).await,
+        },
+
+        Command::Migrate {
+            name,
+
+            to,
+
+            remove_source,
+
+            timeout,
+        } => migrate::migrate(host, name, to, remove_source, timeout).await,
+
+        Command::MigrateLabels { timeout } => migrate_labels::migrate_labels(host, timeout).await,
+
+        Command::Restore {
+            archive,
+
+            name,
+
+            force,
+
+            tls_key,
+
+            env,
+
+            timeout,
+        } => restore::restore(host, archive, name, force, tls_key, env, timeout).await,
+
+        Command::Ca { command } => match command {
+            CaCommand::Export { output } => ca::export(output),
+        },
+
+        Command::Tls { command } => match command {
+            TlsCommand::Set {
+                name,
+
+                cert,
+
+                key,
+
+                hostname,
+
+                timeout,
+            } => tls::set(host, name, cert, key, hostname, timeout).await,
+
+            TlsCommand::Rm { name, timeout } => tls::rm(host, name, timeout).await,
+
+            TlsCommand::Check { days } => tls::check(host, days).await,
+
+            TlsCommand::Renew { days, timeout } => tls::renew(host, days, timeout).await,
+        },
+
+        Command::Clone {
+            name,
+
+            new_name,
+
+            port,
+
+            no_data,
+
+            timeout,
+        } => clone::clone(host, name, new_name, port, no_data, timeout).await,
+
+        Command::Doctor => doctor::doctor(host).await,
+
+        Command::Export { name, format, output } => export::export(host, name, format, output).await,
+
+        Command::Import { file, service, timeout } => import::import(host, file, service, timeout).await,
+
+        Command::Inspect { name } => inspect::inspect(host, name).await,
+
+        Command::Library { command } => match command {
+            LibraryCommand::Add {
+                server,
+
+                name,
+
+                path,
+
+                timeout,
+            } => library::add(host, server, name, path, timeout).await,
+
+            LibraryCommand::Rm { server, name, timeout } => library::rm(host, server, name, timeout).await,
+        },
+
+        Command::Create {
+            timeout,
+
+            env,
+
+            env_file,
+
+            secret,
+        } => create::create(host, timeout, env, env_file, secret).await,
+    }
+}
@@ -0,0 +1,546 @@
+use crate::error::CertificateError;
+use crate::error::FileError;
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::IpAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use rcgen::BasicConstraints;
+use rcgen::CertificateParams;
+use rcgen::DistinguishedName;
+use rcgen::DnType;
+use rcgen::ExtendedKeyUsagePurpose;
+use rcgen::IsCa;
+use rcgen::Issuer;
+use rcgen::KeyPair;
+use rcgen::KeyUsagePurpose;
+
+use rustls::pki_types::CertificateDer;
+
+use time::Duration;
+use time::OffsetDateTime;
+
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::FromDer;
+use x509_parser::time::ASN1Time;
+use x509_parser::x509::SubjectPublicKeyInfo;
+
+/// Browsers refuse leaf certificates that are valid for longer than this.
+const LEAF_VALIDITY: Duration = Duration::days(397);
+
+const CA_VALIDITY: Duration = Duration::days(3650);
+
+/// The end of the validity period of the first certificate in a PEM chain.
+pub fn not_after(chain: &str) -> Result<OffsetDateTime, CertificateError> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(chain.as_bytes())
+        //
+        .map_err(CertificateError::parse("not a PEM file"))?;
+
+    let certificate = pem
+        //
+        .parse_x509()
+        //
+        .map_err(CertificateError::parse("not a certificate"))?;
+
+    Ok(certificate.validity().not_after.to_datetime())
+}
+
+/// Whole days left until the first certificate of a PEM chain expires, negative once it has.
+pub fn days_until_expiry(chain: &str) -> Result<i64, CertificateError> {
+    Ok(days_until(not_after(chain)?))
+}
+
+/// Whole days left until `not_after`, rounded down so that a certificate that expired an hour ago has -1.
+pub fn days_until(not_after: OffsetDateTime) -> i64 {
+    const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+    (not_after - OffsetDateTime::now_utc())
+        //
+        .whole_seconds()
+        //
+        .div_euclid(SECONDS_PER_DAY)
+}
+
+/// Checks a certificate chain, its private key and optionally the hostnames it should cover.
+///
+/// Problems that would prevent the server from working are errors; the returned warnings are problems that only some
+/// clients would notice.
+pub fn check(chain: &str, key: &str, hostnames: &[String]) -> Result<Vec<String>, CertificateError> {
+    let mut warnings = check_chain(chain)?;
+
+    check_pair(chain, key)?;
+
+    warnings.extend(check_hostnames(chain, hostnames)?);
+
+    Ok(warnings)
+}
+
+/// Parses every certificate of a PEM chain, keeping the DER around for the parsed views to borrow from.
+fn parse_chain(chain: &str) -> Result<Vec<CertificateDer<'static>>, CertificateError> {
+    let ders = rustls_pemfile::certs(&mut chain.as_bytes())
+        //
+        .collect::<::std::result::Result<Vec<_>, _>>()
+        //
+        .map_err(CertificateError::parse("the certificate file is not a valid PEM file"))?;
+
+    if ders.is_empty() {
+        return Err(CertificateError::Invalid(
+            "the certificate file contains no certificate".to_string(),
+        ));
+    }
+
+    Ok(ders)
+}
+
+fn parse_certificates<'a>(ders: &'a [CertificateDer<'static>]) -> Result<Vec<X509Certificate<'a>>, CertificateError> {
+    ders
+        //
+        .iter()
+        //
+        .enumerate()
+        //
+        .map(|(index, der)| {
+            X509Certificate::from_der(der)
+                //
+                .map(|(_, certificate)| certificate)
+                //
+                .map_err(CertificateError::parse(format!(
+                    "certificate {} of the chain could not be parsed",
+                    index + 1
+                )))
+        })
+        //
+        .collect()
+}
+
+/// Describes a certificate by its position in the chain and its subject, for error messages.
+fn describe(index: usize, certificate: &X509Certificate) -> String {
+    format!("certificate {} ({})", index + 1, certificate.subject())
+}
+
+/// Makes sure the chain starts with the server's certificate, that every certificate is issued by the next one and
+/// that all of them are currently valid.
+pub fn check_chain(chain: &str) -> Result<Vec<String>, CertificateError> {
+    let ders = parse_chain(chain)?;
+
+    let certificates = parse_certificates(&ders)?;
+
+    let now = ASN1Time::now();
+
+    for (index, certificate) in certificates.iter().enumerate() {
+        let validity = certificate.validity();
+
+        if validity.not_before > now {
+            return Err(CertificateError::Invalid(format!(
+                "{} is not valid before {}",
+                describe(index, certificate),
+                validity.not_before
+            )));
+        }
+
+        if validity.not_after < now {
+            return Err(CertificateError::Invalid(format!(
+                "{} expired on {}",
+                describe(index, certificate),
+                validity.not_after
+            )));
+        }
+    }
+
+    let leaf = &certificates[0];
+
+    if leaf.is_ca() && certificates.len() > 1 {
+        return Err(CertificateError::Invalid(format!(
+            "{} is a CA certificate, the server's own certificate must come first in the file",
+            describe(0, leaf)
+        )));
+    }
+
+    for (index, pair) in certificates.windows(2).enumerate() {
+        let [certificate, issuer] = pair else {
+            unreachable!();
+        };
+
+        if certificate.issuer() != issuer.subject() {
+            return Err(CertificateError::Invalid(format!(
+                "{} is issued by {}, not by the next {}; the chain is in the wrong order or incomplete",
+                describe(index, certificate),
+                certificate.issuer(),
+                describe(index + 1, issuer)
+            )));
+        }
+
+        certificate
+            //
+            .verify_signature(Some(issuer.public_key()))
+            //
+            .map_err(|error| {
+                CertificateError::Invalid(format!(
+                    "the signature of {} cannot be verified with the public key of {}: {error}",
+                    describe(index, certificate),
+                    describe(index + 1, issuer)
+                ))
+            })?;
+    }
+
+    let mut warnings = Vec::new();
+
+    let last = certificates.last().unwrap();
+
+    if certificates.len() == 1 && last.issuer() != last.subject() {
+        warnings.push(format!(
+            "the file only contains the server's certificate, which is issued by {}; unless clients trust that \
+             issuer directly, append the intermediate certificates",
+            last.issuer()
+        ));
+    }
+
+    if let Ok(None) = leaf.subject_alternative_name() {
+        warnings.push(format!(
+            "{} has no subject alternative names, which most clients require",
+            describe(0, leaf)
+        ));
+    }
+
+    Ok(warnings)
+}
+
+/// Makes sure the private key belongs to the first certificate of the chain.
+pub fn check_pair(chain: &str, key: &str) -> Result<(), CertificateError> {
+    let ders = parse_chain(chain)?;
+
+    let certificates = parse_certificates(&ders)?;
+
+    let private_key = rustls_pemfile::private_key(&mut key.as_bytes())
+        //
+        .map_err(CertificateError::parse("the private key is not a PEM file"))?
+        //
+        .ok_or_else(|| CertificateError::Invalid("the private key file contains no private key".to_string()))?;
+
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&private_key)
+        //
+        .map_err(CertificateError::parse("the private key is of an unsupported type"))?;
+
+    let public_key = signing_key
+        //
+        .public_key()
+        //
+        .ok_or_else(|| {
+            CertificateError::Invalid("the public key could not be derived from the private key".to_string())
+        })?;
+
+    let (_, public_key) = SubjectPublicKeyInfo::from_der(&public_key)
+        //
+        .map_err(CertificateError::parse("the public key could not be parsed"))?;
+
+    // comparing the bit strings avoids differences in how the algorithm parameters are encoded
+    if public_key.subject_public_key.data != certificates[0].public_key().subject_public_key.data {
+        return Err(CertificateError::Invalid(format!(
+            "the private key does not match {}",
+            describe(0, &certificates[0])
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns a warning for each hostname or IP address the server's certificate does not cover.
+pub fn check_hostnames(chain: &str, hostnames: &[String]) -> Result<Vec<String>, CertificateError> {
+    let ders = parse_chain(chain)?;
+
+    let certificates = parse_certificates(&ders)?;
+
+    let general_names = certificates[0]
+        //
+        .subject_alternative_name()
+        //
+        .map_err(CertificateError::parse(
+            "the subject alternative names could not be parsed",
+        ))?
+        //
+        .map(|extension| extension.value.general_names.clone())
+        //
+        .unwrap_or_default();
+
+    let warnings = hostnames
+        //
+        .iter()
+        //
+        .filter(|hostname| {
+            !general_names
+                //
+                .iter()
+                //
+                .any(|general_name| covers(general_name, hostname))
+        })
+        //
+        .map(|hostname| format!("{} does not cover {hostname}", describe(0, &certificates[0])))
+        //
+        .collect();
+
+    Ok(warnings)
+}
+
+fn covers(general_name: &GeneralName, hostname: &str) -> bool {
+    match (general_name, hostname.parse::<IpAddr>()) {
+        (GeneralName::IPAddress(bytes), Ok(IpAddr::V4(address))) => *bytes == address.octets(),
+
+        (GeneralName::IPAddress(bytes), Ok(IpAddr::V6(address))) => *bytes == address.octets(),
+
+        (GeneralName::DNSName(name), Err(_)) => {
+            let name = name.to_ascii_lowercase();
+
+            let hostname = hostname.to_ascii_lowercase();
+
+            // a wildcard only stands for exactly one label
+            match name.strip_prefix("*.") {
+                Some(suffix) => hostname
+                    //
+                    .split_once('.')
+                    //
+                    .is_some_and(|(_, rest)| rest == suffix),
+
+                None => name == hostname,
+            }
+        }
+
+        _ => false,
+    }
+}
+
+/// Splits a comma-separated list of hostnames and IP addresses.
+pub fn parse_subject_alt_names(input: &str) -> Result<Vec<String>, CertificateError> {
+    let names = input
+        //
+        .split(',')
+        //
+        .map(str::trim)
+        //
+        .filter(|name| !name.is_empty())
+        //
+        .map(ToString::to_string)
+        //
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        return Err(CertificateError::Invalid(
+            "at least one hostname or IP address is required".to_string(),
+        ));
+    }
+
+    // rcgen rejects names that cannot be encoded
+    CertificateParams::new(names.clone())
+        //
+        .map_err(CertificateError::generate(
+            "not a valid list of hostnames and IP addresses",
+        ))?;
+
+    Ok(names)
+}
+
+fn leaf_params(subject_alt_names: Vec<String>) -> Result<CertificateParams, CertificateError> {
+    let mut params = CertificateParams::new(subject_alt_names.clone())
+        //
+        .map_err(CertificateError::generate(
+            "not a valid list of hostnames and IP addresses",
+        ))?;
+
+    let mut distinguished_name = DistinguishedName::new();
+
+    distinguished_name.push(DnType::CommonName, subject_alt_names[0].clone());
+
+    params.distinguished_name = distinguished_name;
+
+    let now = OffsetDateTime::now_utc();
+
+    params.not_before = now - Duration::days(1);
+
+    params.not_after = now + LEAF_VALIDITY;
+
+    params.key_usages = vec![
+        //
+        KeyUsagePurpose::DigitalSignature,
+        //
+        KeyUsagePurpose::KeyEncipherment,
+    ];
+
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+    Ok(params)
+}
+
+/// Generates a key pair and a self-signed certificate, both PEM encoded.
+pub fn generate_self_signed(subject_alt_names: Vec<String>) -> Result<(String, String), CertificateError> {
+    let key_pair = KeyPair::generate()
+        //
+        .map_err(CertificateError::generate("failed to generate a key pair"))?;
+
+    let certificate = leaf_params(subject_alt_names)?
+        //
+        .self_signed(&key_pair)
+        //
+        .map_err(CertificateError::generate("failed to sign the certificate"))?;
+
+    Ok((certificate.pem(), key_pair.serialize_pem()))
+}
+
+/// Generates a key pair and a certificate signed by the local CA, both PEM encoded.
+///
+/// The CA is created on first use.
+pub fn generate_signed_by_local_ca(subject_alt_names: Vec<String>) -> Result<(String, String), CertificateError> {
+    let LocalCa {
+        //
+        certificate: ca_certificate,
+        //
+        key: ca_key,
+    } = LocalCa::load_or_create()
+        //
+        .map_err(|source| CertificateError::LocalCa(Box::new(source)))?;
+
+    let ca_key_pair = KeyPair::from_pem(&ca_key)
+        //
+        .map_err(CertificateError::generate("failed to parse the local CA's private key"))?;
+
+    let issuer = Issuer::from_ca_cert_pem(&ca_certificate, ca_key_pair)
+        //
+        .map_err(CertificateError::generate("failed to parse the local CA's certificate"))?;
+
+    let key_pair = KeyPair::generate()
+        //
+        .map_err(CertificateError::generate("failed to generate a key pair"))?;
+
+    let certificate = leaf_params(subject_alt_names)?
+        //
+        .signed_by(&key_pair, &issuer)
+        //
+        .map_err(CertificateError::generate("failed to sign the certificate"))?;
+
+    // clients that only trust the CA still need the whole chain from the server
+    Ok((certificate.pem() + &ca_certificate, key_pair.serialize_pem()))
+}
+
+/// A certificate authority kept in the user's data directory, for servers that are only reachable on the LAN.
+///
+/// Installing its certificate on client devices makes them trust every certificate it signs.
+pub struct LocalCa {
+    pub certificate: String,
+
+    pub key: String,
+}
+
+impl LocalCa {
+    pub fn directory() -> Result<PathBuf, CertificateError> {
+        let data_dir = dirs::data_dir()
+            //
+            .ok_or(CertificateError::NoDataDirectory)?;
+
+        Ok(data_dir.join("mayo").join("ca"))
+    }
+
+    pub fn load() -> Result<Option<Self>, CertificateError> {
+        let directory = Self::directory()?;
+
+        let certificate_path = directory.join("ca.crt");
+
+        let key_path = directory.join("ca.key");
+
+        if !certificate_path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            certificate: fs::read_to_string(&certificate_path)
+                //
+                .map_err(FileError::on("read", &certificate_path))?,
+
+            key: fs::read_to_string(&key_path)
+                //
+                .map_err(FileError::on("read", &key_path))?,
+        }))
+    }
+
+    pub fn load_or_create() -> Result<Self, CertificateError> {
+        if let Some(local_ca) = Self::load()? {
+            return Ok(local_ca);
+        }
+
+        let key_pair = KeyPair::generate()
+            //
+            .map_err(CertificateError::generate("failed to generate a key pair"))?;
+
+        let mut params = CertificateParams::default();
+
+        let mut distinguished_name = DistinguishedName::new();
+
+        distinguished_name.push(DnType::CommonName, "mayo local CA");
+
+        params.distinguished_name = distinguished_name;
+
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+
+        let now = OffsetDateTime::now_utc();
+
+        params.not_before = now - Duration::days(1);
+
+        params.not_after = now + CA_VALIDITY;
+
+        params.key_usages = vec![
+            //
+            KeyUsagePurpose::KeyCertSign,
+            //
+            KeyUsagePurpose::CrlSign,
+        ];
+
+        let certificate = params
+            //
+            .self_signed(&key_pair)
+            //
+            .map_err(CertificateError::generate("failed to sign the CA certificate"))?;
+
+        let local_ca = Self {
+            certificate: certificate.pem(),
+
+            key: key_pair.serialize_pem(),
+        };
+
+        let directory = Self::directory()?;
+
+        fs::create_dir_all(&directory)
+            //
+            .map_err(FileError::on("create", &directory))?;
+
+        write_private(&directory.join("ca.key"), &local_ca.key)?;
+
+        let certificate_path = directory.join("ca.crt");
+
+        fs::write(&certificate_path, &local_ca.certificate)
+            //
+            .map_err(FileError::on("write", &certificate_path))?;
+
+        Ok(local_ca)
+    }
+}
+
+/// Writes a file that only the current user can read.
+pub fn write_private(path: &Path, contents: &str) -> Result<(), FileError> {
+    OpenOptions::new()
+        //
+        .write(true)
+        //
+        .create(true)
+        //
+        .truncate(true)
+        //
+        .mode(0o600)
+        //
+        .open(path)
+        //
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        //
+        .map_err(FileError::on("write", path))
+}
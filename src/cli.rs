@@ -1,17 +1,376 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
 
 #[derive(Parser)]
 pub struct Cli {
+    /// Docker host to manage: a host from the config file, a Docker context, or an endpoint such as
+    /// ssh://admin@media-1; ls accepts several
+    #[arg(long = "host", global = true, value_name = "HOST")]
+    pub hosts: Vec<String>,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
+    /// Take over an app container that was started by hand, recreating it as a server on the same data
+    Adopt {
+        /// Name or ID of the container
+        container: String,
+
+        /// Name of the server, instead of the container's
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Adopt without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Create, recreate and optionally remove servers so that they match a manifest file
+    Apply {
+        /// Path of the manifest, e.g. servers.toml
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Remove the servers that are not in the manifest, keeping their data
+        #[arg(long)]
+        prune: bool,
+
+        /// Print the changes without making them
+        #[arg(long)]
+        plan: bool,
+
+        /// Seconds to wait for each server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Back up the data of a server to a portable archive, or manage scheduled backups
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Backup {
+        #[command(subcommand)]
+        command: Option<BackupCommand>,
+
+        /// Name of the server
+        #[arg(required = true)]
+        name: Option<String>,
+
+        /// Path of the archive to write, e.g. backup.tar.zst
+        #[arg(short, long, required = true)]
+        output: Option<PathBuf>,
+    },
+
+    /// Manage the local certificate authority
+    Ca {
+        #[command(subcommand)]
+        command: CaCommand,
+    },
+
+    /// Create a copy of a server, e.g. for staging
+    Clone {
+        /// Name of the server to copy
+        name: String,
+
+        /// Name of the copy, instead of a generated one
+        #[arg(long = "name", value_name = "NAME")]
+        new_name: Option<String>,
+
+        /// Port of the copy, instead of the first free one after the original's
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Start the copy with empty data
+        #[arg(long)]
+        no_data: bool,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
     /// Create and run a new server
-    Create,
+    Create {
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// Extra environment variable for the app, as KEY=VALUE
+        #[arg(long, value_name = "KEY=VALUE")]
+        env: Vec<String>,
+
+        /// File with one KEY=VALUE pair per line
+        #[arg(long, value_name = "PATH")]
+        env_file: Vec<PathBuf>,
+
+        /// Key of an environment variable whose value should never be displayed
+        #[arg(long, value_name = "KEY")]
+        secret: Vec<String>,
+    },
+
+    /// Show which container engine serves the host, and what to be aware of on it
+    Doctor,
+
+    /// Write a server's container configuration to a file, e.g. to run it with Docker Compose
+    Export {
+        /// Name of the server
+        name: String,
+
+        /// Format of the file
+        #[arg(long, value_enum, default_value_t = ExportFormat::Compose)]
+        format: ExportFormat,
+
+        /// Directory to write docker-compose.yml, and the env file with the server's secrets, into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Create a server from a compose file, e.g. one written by export
+    Import {
+        /// Path of the compose file, e.g. docker-compose.yml
+        file: PathBuf,
+
+        /// Service to import, when the file has several servers
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Show the details of a server
+    Inspect {
+        /// Name of the server
+        name: String,
+    },
+
+    /// Manage the music libraries mounted into a server
+    Library {
+        #[command(subcommand)]
+        command: LibraryCommand,
+    },
 
     /// List servers
-    Ls,
+    Ls {
+        /// Also list containers this CLI cannot manage, with the reason
+        #[arg(long)]
+        all: bool,
+
+        /// List the servers of every host in the config file
+        #[arg(long)]
+        all_hosts: bool,
+
+        /// Seconds to wait for each host when listing several
+        #[arg(long, default_value_t = 10)]
+        host_timeout: u64,
+
+        /// Highlight certificates that expire within this many days
+        #[arg(long, default_value_t = 30)]
+        warn_days: i64,
+    },
+
+    /// Move a server, with its data, to another Docker daemon
+    Migrate {
+        /// Name of the server
+        name: String,
+
+        /// Docker host to move the server to, e.g. a host from the config file or tcp://10.0.0.2:2375
+        #[arg(long)]
+        to: String,
+
+        /// Remove the source server and its data once the server is healthy on the target
+        #[arg(long)]
+        remove_source: bool,
+
+        /// Seconds to wait for the server to become healthy on the target
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Rewrite the labels of servers created by older CLIs to the current schema, recreating their containers
+    MigrateLabels {
+        /// Seconds to wait for each server to become healthy again
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Create a server from a backup archive
+    Restore {
+        /// Path of the archive to read
+        archive: PathBuf,
+
+        /// Name of the new server, instead of the one in the archive
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replace an existing server or data with the same name
+        #[arg(long)]
+        force: bool,
+
+        /// Private key of the archived TLS certificate, which backups do not contain
+        #[arg(long, value_name = "PATH")]
+        tls_key: Option<PathBuf>,
+
+        /// Value of a secret environment variable, which backups do not contain, as KEY=VALUE
+        #[arg(long, value_name = "KEY=VALUE")]
+        env: Vec<String>,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Manage the TLS certificates of servers
+    Tls {
+        #[command(subcommand)]
+        command: TlsCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LibraryCommand {
+    /// Mount a host directory into a server, read-only
+    Add {
+        /// Name of the server
+        server: String,
+
+        /// Name of the library
+        name: String,
+
+        /// Path to the host directory
+        path: PathBuf,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Unmount a library from a server
+    Rm {
+        /// Name of the server
+        server: String,
+
+        /// Name of the library
+        name: String,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Back up a server on a schedule, replacing its previous schedule
+    Schedule {
+        /// Name of the server
+        name: String,
+
+        /// When to back up, as a five-field cron expression in local time, e.g. "0 3 * * *"
+        #[arg(long)]
+        cron: String,
+
+        /// Directory to write the archives to
+        #[arg(long)]
+        directory: PathBuf,
+
+        /// Keep this many of the newest archives
+        #[arg(long)]
+        keep_last: Option<usize>,
+
+        /// Keep the newest archive of each of this many days
+        #[arg(long)]
+        keep_daily: Option<usize>,
+
+        /// Keep the newest archive of each of this many weeks
+        #[arg(long)]
+        keep_weekly: Option<usize>,
+    },
+
+    /// Stop backing up a server on a schedule, keeping its archives
+    Unschedule {
+        /// Name of the server
+        name: String,
+    },
+
+    /// Take the scheduled backups that are due and prune old archives, e.g. from cron every few minutes
+    RunDue,
+}
+
+#[derive(Subcommand)]
+pub enum CaCommand {
+    /// Print the local CA's certificate, for installation on client devices
+    Export {
+        /// Write the certificate to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TlsCommand {
+    /// Replace the certificate and private key of a server
+    Set {
+        /// Name of the server
+        name: String,
+
+        /// Path to the PEM certificate chain
+        #[arg(long)]
+        cert: PathBuf,
+
+        /// Path to the PEM private key
+        #[arg(long)]
+        key: PathBuf,
+
+        /// Hostname or IP address the certificate should cover, to be warned if it does not
+        #[arg(long)]
+        hostname: Vec<String>,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Turn TLS off for a server
+    Rm {
+        /// Name of the server
+        name: String,
+
+        /// Seconds to wait for the server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Exit with an error if any certificate expires within the given number of days
+    Check {
+        /// Number of days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+    },
+
+    /// Renew the ACME certificates that are about to expire, e.g. from a daily cron job
+    Renew {
+        /// Renew certificates that expire within this many days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+
+        /// Seconds to wait for each server to become healthy
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// A docker-compose.yml with the server as a service
+    Compose,
 }
@@ -1,2 +0,0 @@
-pub mod create;
-pub mod ls;
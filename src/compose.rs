@@ -0,0 +1,928 @@
+//! Docker Compose files that run a server the way the CLI does, and servers imported back from them.
+//!
+//! A server is exported as a service with the container configuration that
+//! [`Mayo::create_server`](crate::mayo::Mayo::create_server) would build, labels included, so that the container is
+//! listed as a server whichever way it was started. Secrets are not written into the compose file: the private key and
+//! the secret variables go into an env file next to it, and the parameters label leaves their values out. Importing
+//! reads the parameters back from that label, and the secret values from the service's env files.
+//!
+//! A service of the app without that label, e.g. from a compose file written by hand, is mapped the way an adopted
+//! container is, from its environment and its volumes.
+
+use crate::adopt;
+use crate::adopt::Adoption;
+use crate::error::ComposeError;
+use crate::error::FileError;
+use crate::labels::*;
+use crate::parameters::Environment;
+use crate::parameters::Parameters;
+use crate::parameters::Tls;
+use crate::registry;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use bollard::container;
+use bollard::models::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+const SECOND: i64 = 1_000_000_000;
+
+/// A compose file, and the env file holding the secrets it refers to.
+#[derive(Clone, Debug)]
+pub struct Export {
+    pub compose: String,
+
+    /// Name of the env file, relative to the compose file, and its contents; absent when there are no secrets.
+    pub env_file: Option<(String, String)>,
+}
+
+/// A server read back from a compose file.
+#[derive(Clone, Debug)]
+pub struct Import {
+    pub parameters: Parameters,
+
+    pub origin: Option<String>,
+
+    /// Secrets without a value in the env files, which are left out of the server. A missing private key disables TLS.
+    pub missing_secrets: Vec<String>,
+
+    /// Settings of a service without a parameters label that the server will not have.
+    pub unmapped: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ComposeFile {
+    services: BTreeMap<String, Service>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    volumes: BTreeMap<String, Volume>,
+}
+
+#[derive(Default, Serialize)]
+struct Service {
+    image: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    entrypoint: Vec<String>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    environment: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    env_file: Vec<String>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    volumes: Vec<ServiceVolume>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network_mode: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restart: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    healthcheck: Option<Healthcheck>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cap_drop: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    security_opt: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_only: Option<bool>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tmpfs: Vec<String>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    depends_on: BTreeMap<String, Dependency>,
+}
+
+#[derive(Serialize)]
+struct ServiceVolume {
+    #[serde(rename = "type")]
+    typ: String,
+
+    source: String,
+
+    target: String,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    read_only: bool,
+}
+
+#[derive(Serialize)]
+struct Volume {
+    /// Set so that compose does not prefix the volume with the project's name, and uses the server's volume.
+    name: String,
+}
+
+#[derive(Serialize)]
+struct Healthcheck {
+    test: Vec<String>,
+
+    interval: String,
+
+    timeout: String,
+
+    retries: i64,
+
+    start_period: String,
+}
+
+#[derive(Serialize)]
+struct Dependency {
+    condition: &'static str,
+}
+
+/// What is read of a compose file on import; the rest is rebuilt from the parameters label.
+#[derive(Deserialize)]
+struct ImportedFile {
+    #[serde(default)]
+    services: BTreeMap<String, ImportedService>,
+
+    #[serde(default)]
+    volumes: BTreeMap<String, Option<ImportedVolume>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ImportedService {
+    image: String,
+
+    container_name: Option<String>,
+
+    user: Option<String>,
+
+    labels: MapOrList,
+
+    environment: MapOrList,
+
+    env_file: StringOrList,
+
+    volumes: Vec<ImportedServiceVolume>,
+
+    network_mode: Option<String>,
+
+    ports: Vec<serde_yaml_ng::Value>,
+}
+
+/// A mount of a service, as `SOURCE:TARGET[:MODE]` or with its fields spelled out.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportedServiceVolume {
+    Short(String),
+
+    Long {
+        #[serde(rename = "type")]
+        typ: String,
+
+        #[serde(default)]
+        source: String,
+
+        target: String,
+    },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ImportedVolume {
+    name: Option<String>,
+}
+
+/// Compose accepts both `KEY: VALUE` maps and `KEY=VALUE` lists for labels and the environment.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MapOrList {
+    Map(BTreeMap<String, Option<String>>),
+
+    List(Vec<String>),
+}
+
+impl Default for MapOrList {
+    fn default() -> Self {
+        Self::List(Vec::new())
+    }
+}
+
+impl MapOrList {
+    fn into_map(self) -> BTreeMap<String, String> {
+        match self {
+            Self::Map(map) => map
+                //
+                .into_iter()
+                //
+                .map(|(key, value)| (key, value.unwrap_or_default()))
+                //
+                .collect(),
+
+            Self::List(list) => list
+                //
+                .into_iter()
+                //
+                .map(|pair| match pair.split_once('=') {
+                    Some((key, value)) => (key.to_string(), value.to_string()),
+
+                    None => (pair, String::new()),
+                })
+                //
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    String(String),
+
+    List(Vec<String>),
+}
+
+impl Default for StringOrList {
+    fn default() -> Self {
+        Self::List(Vec::new())
+    }
+}
+
+/// Renders the configuration of a server's container as a compose file.
+///
+/// `preparation` is the configuration of the container that prepares a hardened server's data volume, which becomes a
+/// service the server depends on.
+pub(crate) fn render(
+    parameters: &Parameters,
+    config: container::Config<String>,
+    preparation: Option<container::Config<String>>,
+) -> Result<Export, ComposeError> {
+    let name = parameters.name.to_string();
+
+    let env_file_name = format!("mayo-{name}.env");
+
+    let secret_keys = parameters
+        //
+        .environment
+        //
+        .iter()
+        //
+        .filter(|(_, variable)| variable.secret)
+        //
+        .map(|(key, _)| key.as_str())
+        //
+        .chain(["MAYO_TLS_KEY"])
+        //
+        .collect::<Vec<_>>();
+
+    let mut service = service(config);
+
+    let mut secrets = String::new();
+
+    service.environment.retain(|key, value| {
+        if !secret_keys.contains(&key.as_str()) {
+            return true;
+        }
+
+        secrets.push_str(&format!("{key}={}\n", quote(&unescape_interpolation(value))));
+
+        false
+    });
+
+    if !secrets.is_empty() {
+        service.env_file.push(env_file_name.clone());
+    }
+
+    // the values left out here are the ones in the env file
+    service.labels.insert(
+        //
+        LABEL_KEY_PARAMETERS.to_string(),
+        //
+        escape_interpolation(&encode_parameters(&parameters.without_secrets())),
+    );
+
+    service.container_name = Some(format!("mayo-{name}"));
+
+    let mut services = BTreeMap::new();
+
+    if let Some(preparation) = preparation {
+        let preparation_name = format!("{name}-prepare-data");
+
+        service.depends_on.insert(
+            //
+            preparation_name.clone(),
+            //
+            Dependency {
+                condition: "service_completed_successfully",
+            },
+        );
+
+        services.insert(preparation_name, self::service(preparation));
+    }
+
+    let volumes = service
+        //
+        .volumes
+        //
+        .iter()
+        //
+        .filter(|volume| volume.typ == "volume")
+        //
+        .map(|volume| {
+            (
+                volume.source.clone(),
+                Volume {
+                    name: volume.source.clone(),
+                },
+            )
+        })
+        //
+        .collect();
+
+    services.insert(name, service);
+
+    let compose = serde_yaml_ng::to_string(&ComposeFile { services, volumes })
+        //
+        .map_err(ComposeError::Encode)?;
+
+    Ok(Export {
+        compose,
+
+        env_file: (!secrets.is_empty()).then_some((env_file_name, secrets)),
+    })
+}
+
+fn service(config: container::Config<String>) -> Service {
+    let container::Config {
+        //
+        image,
+        //
+        user,
+        //
+        entrypoint,
+        //
+        env,
+        //
+        labels,
+        //
+        healthcheck,
+        //
+        host_config,
+        ..
+    } = config;
+
+    let environment = env
+        //
+        .unwrap_or_default()
+        //
+        .into_iter()
+        //
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), escape_interpolation(value)),
+
+            None => (pair, String::new()),
+        })
+        //
+        .collect();
+
+    let labels = labels
+        //
+        .unwrap_or_default()
+        //
+        .into_iter()
+        //
+        .map(|(key, value)| (key, escape_interpolation(&value)))
+        //
+        .collect();
+
+    let healthcheck = healthcheck.map(|healthcheck| Healthcheck {
+        test: healthcheck.test.unwrap_or_default(),
+
+        interval: duration(healthcheck.interval.unwrap_or_default()),
+
+        timeout: duration(healthcheck.timeout.unwrap_or_default()),
+
+        retries: healthcheck.retries.unwrap_or_default(),
+
+        start_period: duration(healthcheck.start_period.unwrap_or_default()),
+    });
+
+    let host_config = host_config.unwrap_or_default();
+
+    let volumes = host_config
+        //
+        .mounts
+        //
+        .unwrap_or_default()
+        //
+        .into_iter()
+        //
+        .map(|mount| ServiceVolume {
+            typ: mount.typ.map(|typ| typ.to_string()).unwrap_or_default(),
+
+            source: mount.source.unwrap_or_default(),
+
+            target: mount.target.unwrap_or_default(),
+
+            read_only: mount.read_only.unwrap_or_default(),
+        })
+        //
+        .collect();
+
+    let tmpfs = host_config
+        //
+        .tmpfs
+        //
+        .unwrap_or_default()
+        //
+        .into_iter()
+        //
+        .map(|(path, options)| format!("{path}:{options}"))
+        //
+        .collect();
+
+    let restart = host_config
+        //
+        .restart_policy
+        //
+        .and_then(|policy| policy.name)
+        //
+        .map(|name| name.to_string());
+
+    Service {
+        image: image.unwrap_or_default(),
+
+        user,
+
+        entrypoint: entrypoint.unwrap_or_default(),
+
+        environment,
+
+        labels,
+
+        volumes,
+
+        network_mode: host_config.network_mode,
+
+        restart,
+
+        healthcheck,
+
+        cap_drop: host_config.cap_drop.unwrap_or_default(),
+
+        security_opt: host_config.security_opt.unwrap_or_default(),
+
+        read_only: host_config.readonly_rootfs,
+
+        tmpfs,
+
+        ..Default::default()
+    }
+}
+
+/// Reads the server described by a compose file, from the service named `service` or the only one that is a server.
+pub fn import(path: &Path, service: Option<&str>) -> Result<Import, ComposeError> {
+    let contents = fs::read_to_string(path)
+        //
+        .map_err(FileError::on("read", path))?;
+
+    let file = serde_yaml_ng::from_str::<ImportedFile>(&contents)
+        //
+        .map_err(|source| ComposeError::Parse {
+            path: path.to_path_buf(),
+
+            source,
+        })?;
+
+    let mut servers = file
+        //
+        .services
+        //
+        .into_iter()
+        //
+        .map(|(name, mut service)| {
+            let labels = mem::take(&mut service.labels).into_map();
+
+            (name, labels, service)
+        })
+        //
+        .filter(|(name, labels, imported)| match service {
+            Some(service) => name == service,
+
+            None => labels.contains_key(LABEL_KEY_PARAMETERS) || is_app_image(&imported.image),
+        })
+        //
+        .collect::<Vec<_>>();
+
+    let (name, mut labels, mut service) = match servers.len() {
+        0 => return Err(ComposeError::NoServer(path.to_path_buf())),
+
+        1 => servers.remove(0),
+
+        _ => {
+            let names = servers
+                //
+                .into_iter()
+                //
+                .map(|(name, _, _)| name)
+                //
+                .collect();
+
+            return Err(ComposeError::SeveralServers(names));
+        }
+    };
+
+    // the service's own environment takes precedence over its env files, as with compose
+    let mut values = BTreeMap::new();
+
+    let directory = path
+        //
+        .parent()
+        //
+        .unwrap_or(Path::new(""));
+
+    let env_files = match mem::take(&mut service.env_file) {
+        StringOrList::String(file) => vec![file],
+
+        StringOrList::List(files) => files,
+    };
+
+    for file in env_files {
+        let file = directory.join(file);
+
+        let contents = fs::read_to_string(&file)
+            //
+            .map_err(FileError::on("read", &file))?;
+
+        values.extend(parse_env_file(&contents));
+    }
+
+    values.extend(
+        mem::take(&mut service.environment)
+            //
+            .into_map()
+            //
+            .into_iter()
+            //
+            .map(|(key, value)| (key, unescape_interpolation(&value))),
+    );
+
+    let Some(label) = labels.remove(LABEL_KEY_PARAMETERS) else {
+        return map_service(name, service, values, &file.volumes, directory);
+    };
+
+    let mut parameters = decode_parameters(&unescape_interpolation(&label))
+        //
+        .map_err(|source| ComposeError::MalformedLabel { service: name, source })?
+        //
+        .parameters;
+
+    let origin = labels
+        //
+        .remove(LABEL_KEY_ORIGIN)
+        //
+        .map(|origin| unescape_interpolation(&origin));
+
+    let mut missing_secrets = Vec::new();
+
+    if let Some(crt) = parameters.tls.certificate() {
+        match values.get("MAYO_TLS_KEY").filter(|key| !key.is_empty()) {
+            Some(key) => parameters.tls = Tls::new(crt.to_string(), key.clone()),
+
+            None => {
+                missing_secrets.push("MAYO_TLS_KEY".to_string());
+
+                parameters.tls = Tls::disabled();
+            }
+        }
+    }
+
+    let mut secret_values = Environment::default();
+
+    for (key, value) in values {
+        if Environment::RESERVED_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+
+        secret_values
+            //
+            .insert_pair(&format!("{key}={value}"))
+            //
+            .map_err(ComposeError::InvalidVariable)?;
+    }
+
+    missing_secrets.extend(parameters.environment.fill_secret_values(&secret_values));
+
+    Ok(Import {
+        parameters,
+
+        origin,
+
+        missing_secrets,
+
+        unmapped: Vec::new(),
+    })
+}
+
+/// Whether an image is the app's, whatever its tag.
+fn is_app_image(image: &str) -> bool {
+    let repository = format!("{}/{}/{}", registry::REGISTRY, registry::USERNAME, registry::REPOSITORY);
+
+    let repo = image
+        //
+        .split_once(':')
+        //
+        .map_or(image, |(repo, _)| repo);
+
+    repo == repository
+}
+
+/// Reconstructs the server of a service without a parameters label, as if its container were adopted.
+///
+/// `values` are the variables of its environment and env files. Nothing tells which of them are secret, so they are all
+/// kept as plain variables.
+fn map_service(
+    name: String,
+    service: ImportedService,
+    values: BTreeMap<String, String>,
+    volumes: &BTreeMap<String, Option<ImportedVolume>>,
+    directory: &Path,
+) -> Result<Import, ComposeError> {
+    let ImportedService {
+        //
+        image,
+        //
+        container_name,
+        //
+        user,
+        //
+        volumes: service_volumes,
+        //
+        network_mode,
+        //
+        ports,
+        ..
+    } = service;
+
+    let mounts = service_volumes
+        //
+        .into_iter()
+        //
+        .map(|volume| mount_point(volume, volumes, directory))
+        //
+        .collect();
+
+    let port_bindings = ports
+        //
+        .iter()
+        //
+        .map(|port| (port_spec(port), None))
+        //
+        .collect();
+
+    let env = values
+        //
+        .into_iter()
+        //
+        .map(|(key, value)| format!("{key}={value}"))
+        //
+        .collect();
+
+    let container = ContainerInspectResponse {
+        id: Some(name.clone()),
+
+        name: Some(container_name.unwrap_or_else(|| name.clone())),
+
+        config: Some(ContainerConfig {
+            image: Some(image.clone()),
+
+            user,
+
+            env: Some(env),
+
+            ..Default::default()
+        }),
+
+        host_config: Some(HostConfig {
+            // compose puts a service on the project's network unless told otherwise
+            network_mode: Some(network_mode.unwrap_or_else(|| "default".to_string())),
+
+            port_bindings: Some(port_bindings),
+
+            ..Default::default()
+        }),
+
+        mounts: Some(mounts),
+
+        ..Default::default()
+    };
+
+    let Adoption {
+        parameters,
+
+        copy_from,
+
+        mut unmapped,
+        ..
+    } = adopt::map_container(container, &[image], &[], None)
+        //
+        .map_err(|source| ComposeError::Unmappable { service: name, source })?;
+
+    if let Some(volume) = copy_from {
+        unmapped.push(format!("the data in the volume {volume}, which is not copied"));
+    }
+
+    Ok(Import {
+        parameters,
+
+        origin: None,
+
+        missing_secrets: Vec::new(),
+
+        unmapped,
+    })
+}
+
+/// A published port as written in the compose file, e.g. `8443:8443`.
+fn port_spec(port: &serde_yaml_ng::Value) -> String {
+    match port {
+        serde_yaml_ng::Value::String(port) => port.clone(),
+
+        serde_yaml_ng::Value::Number(port) => port.to_string(),
+
+        port => serde_yaml_ng::to_string(port)
+            //
+            .map(|port| port.trim().replace('\n', ", "))
+            //
+            .unwrap_or_default(),
+    }
+}
+
+/// The mount of a service volume, with bind sources resolved against the compose file's directory.
+fn mount_point(
+    volume: ImportedServiceVolume,
+    volumes: &BTreeMap<String, Option<ImportedVolume>>,
+    directory: &Path,
+) -> MountPoint {
+    let (typ, source, destination) = match volume {
+        ImportedServiceVolume::Short(spec) => {
+            let mut parts = spec.splitn(3, ':');
+
+            let first = parts.next().unwrap_or_default().to_string();
+
+            match parts.next() {
+                Some(target) if first.starts_with(['/', '.', '~']) => ("bind".to_string(), first, target.to_string()),
+
+                Some(target) => ("volume".to_string(), first, target.to_string()),
+
+                // an anonymous volume
+                None => ("volume".to_string(), String::new(), first),
+            }
+        }
+
+        ImportedServiceVolume::Long { typ, source, target } => (typ, source, target),
+    };
+
+    let (typ, name, source) = match typ.as_str() {
+        "bind" => {
+            let path = directory.join(source.trim_start_matches("./"));
+
+            (MountPointTypeEnum::BIND, None, path.to_string_lossy().to_string())
+        }
+
+        "volume" if !source.is_empty() => {
+            let name = volumes
+                //
+                .get(&source)
+                //
+                .and_then(|volume| volume.as_ref()?.name.clone())
+                //
+                .unwrap_or(source);
+
+            (MountPointTypeEnum::VOLUME, Some(name.clone()), name)
+        }
+
+        "volume" => (MountPointTypeEnum::VOLUME, None, source),
+
+        _ => (MountPointTypeEnum::EMPTY, None, source),
+    };
+
+    MountPoint {
+        typ: Some(typ),
+
+        name,
+
+        source: Some(source),
+
+        destination: Some(destination),
+
+        ..Default::default()
+    }
+}
+
+/// `KEY=VALUE` lines, with values optionally in single quotes, taken literally, or double quotes, with `\` escapes.
+fn parse_env_file(contents: &str) -> Vec<(String, String)> {
+    contents
+        //
+        .lines()
+        //
+        .map(str::trim)
+        //
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        //
+        .filter_map(|line| line.split_once('='))
+        //
+        .map(|(key, value)| {
+            let key = key.trim().trim_start_matches("export ").trim().to_string();
+
+            let value = value.trim();
+
+            let value = if let Some(value) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+                unquote(value)
+            } else if let Some(value) = value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')) {
+                value.to_string()
+            } else {
+                value.to_string()
+            };
+
+            (key, value)
+        })
+        //
+        .collect()
+}
+
+/// A double-quoted env file value, on one line whatever it contains.
+fn quote(value: &str) -> String {
+    let mut result = String::from('"');
+
+    for character in value.chars() {
+        match character {
+            '\\' => result.push_str("\\\\"),
+
+            '"' => result.push_str("\\\""),
+
+            '\n' => result.push_str("\\n"),
+
+            '$' => result.push_str("\\$"),
+
+            _ => result.push(character),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
+fn unquote(value: &str) -> String {
+    let mut result = String::new();
+
+    let mut characters = value.chars();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+
+            continue;
+        }
+
+        match characters.next() {
+            Some('n') => result.push('\n'),
+
+            Some(character) => result.push(character),
+
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Compose substitutes `$VARIABLE` in the values of the compose file, and reads `$$` as a literal `$`.
+fn escape_interpolation(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+fn unescape_interpolation(value: &str) -> String {
+    value.replace("$$", "$")
+}
+
+// e.g. 5s, or 1500ms
+fn duration(nanoseconds: i64) -> String {
+    if nanoseconds % SECOND == 0 {
+        format!("{}s", nanoseconds / SECOND)
+    } else {
+        format!("{}ms", nanoseconds / 1_000_000)
+    }
+}
@@ -0,0 +1,86 @@
+//! Settings of the CLI itself, as opposed to the parameters of a server, which live in its container's labels.
+//!
+//! The config file is `mayo/config.toml` in the user's config directory, e.g. `~/.config/mayo/config.toml` on Linux.
+//! It is optional, and meant to be edited by hand as well as by the CLI.
+
+use crate::error::ConfigError;
+use crate::error::FileError;
+use crate::host::HostEntry;
+use crate::schedule::Schedule;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    /// Docker hosts, by the name given to `--host`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, HostEntry>,
+
+    /// Backup schedules, by server name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub backups: BTreeMap<String, Schedule>,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        let config_dir = dirs::config_dir()
+            //
+            .ok_or(ConfigError::NoConfigDirectory)?;
+
+        Ok(config_dir.join("mayo").join("config.toml"))
+    }
+
+    /// Loads the config file, or the default config when there is none.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path()?;
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+
+            Err(error) => return Err(FileError::on("read", path)(error).into()),
+        };
+
+        toml::from_str(&contents)
+            //
+            .map_err(|source| ConfigError::Parse { path, source })
+    }
+
+    /// Writes the config file, replacing it atomically.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::path()?;
+
+        let directory = path
+            //
+            .parent()
+            //
+            .ok_or(ConfigError::NoConfigDirectory)?;
+
+        fs::create_dir_all(directory)
+            //
+            .map_err(FileError::on("create", directory))?;
+
+        let contents = toml::to_string_pretty(self)
+            //
+            .map_err(ConfigError::Encode)?;
+
+        let partial_path = path.with_extension("toml.partial");
+
+        fs::write(&partial_path, contents)
+            //
+            .map_err(FileError::on("write", &partial_path))?;
+
+        fs::rename(&partial_path, &path)
+            //
+            .map_err(FileError::on("replace", &path))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use crate::engine::Engine;
+use crate::error::EngineError;
+use crate::host::Endpoint;
+use crate::host::Host;
+use crate::host::TlsFiles;
+use crate::host::Tunnel;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use bollard::API_DEFAULT_VERSION;
+use bollard::Docker;
+use bollard::container;
+use bollard::container::CreateContainerOptions;
+use bollard::container::DownloadFromContainerOptions;
+use bollard::container::InspectContainerOptions;
+use bollard::container::ListContainersOptions;
+use bollard::container::LogOutput;
+use bollard::container::LogsOptions;
+use bollard::container::RemoveContainerOptions;
+use bollard::container::RenameContainerOptions;
+use bollard::container::StartContainerOptions;
+use bollard::container::StopContainerOptions;
+use bollard::container::UploadToContainerOptions;
+use bollard::container::WaitContainerOptions;
+use bollard::errors::Error as DockerError;
+use bollard::image::CreateImageOptions;
+use bollard::image::ImportImageOptions;
+use bollard::image::ListImagesOptions;
+use bollard::models::*;
+use bollard::system::Version;
+use bollard::volume::CreateVolumeOptions;
+
+use bytes::Bytes;
+
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+use futures_util::future;
+use futures_util::stream::BoxStream;
+
+/// Seconds to wait for a response of the daemon, as in `Docker::connect_with_defaults`.
+const DOCKER_TIMEOUT: u64 = 120;
+
+/// A Docker daemon, or anything else that speaks its API, such as Podman.
+pub struct DockerEngine {
+    docker: Docker,
+
+    /// Kept open for as long as the daemon is used through it.
+    _tunnel: Option<Tunnel>,
+}
+
+impl From<Docker> for DockerEngine {
+    fn from(docker: Docker) -> Self {
+        Self { docker, _tunnel: None }
+    }
+}
+
+impl DockerEngine {
+    pub async fn connect(host: &Host) -> Result<Self, EngineError> {
+        let mut tunnel = None;
+
+        let docker = match &host.endpoint {
+            Endpoint::Unix(path) => Docker::connect_with_unix(path, DOCKER_TIMEOUT, API_DEFAULT_VERSION),
+
+            Endpoint::Tcp { address, tls: None } => {
+                Docker::connect_with_http(address, DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+            }
+
+            Endpoint::Tcp {
+                address,
+
+                tls: Some(TlsFiles { ca, cert, key }),
+            } => Docker::connect_with_ssl(address, key, cert, ca, DOCKER_TIMEOUT, API_DEFAULT_VERSION),
+
+            Endpoint::Ssh {
+                destination,
+
+                port,
+
+                socket,
+            } => {
+                let opened = Tunnel::open(destination, *port, socket)
+                    //
+                    .await
+                    //
+                    .map_err(EngineError::Tunnel)?;
+
+                let path = format!("unix://{}", opened.socket.display());
+
+                tunnel = Some(opened);
+
+                Docker::connect_with_unix(&path, DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+            }
+        };
+
+        let docker = docker
+            //
+            .map_err(|source| EngineError::Connect {
+                host: host.name.clone(),
+
+                source,
+            })?;
+
+        Ok(Self {
+            docker,
+
+            _tunnel: tunnel,
+        })
+    }
+}
+
+impl Engine for DockerEngine {
+    async fn version(&self) -> Result<Version, EngineError> {
+        Ok(self.docker.version().await?)
+    }
+
+    async fn info(&self) -> Result<SystemInfo, EngineError> {
+        Ok(self.docker.info().await?)
+    }
+
+    fn pull_image(&self, reference: &str) -> BoxStream<'_, Result<CreateImageInfo, EngineError>> {
+        let options = CreateImageOptions {
+            //
+            from_image: reference.to_string(),
+            //
+            ..Default::default()
+        };
+
+        self
+            //
+            .docker
+            //
+            .create_image(Some(options), None, None)
+            //
+            .map_err(Into::into)
+            //
+            .boxed()
+    }
+
+    async fn list_images(&self, reference: &str) -> Result<Vec<ImageSummary>, EngineError> {
+        let mut filters = HashMap::default();
+
+        filters.insert("reference".to_string(), vec![reference.to_string()]);
+
+        let options = ListImagesOptions {
+            all: false,
+
+            filters,
+
+            digests: false,
+        };
+
+        Ok(self.docker.list_images(Some(options)).await?)
+    }
+
+    async fn inspect_image(&self, id: &str) -> Result<ImageInspect, EngineError> {
+        Ok(self.docker.inspect_image(id).await?)
+    }
+
+    fn export_image(&self, reference: &str) -> BoxStream<'_, Result<Bytes, EngineError>> {
+        self
+            //
+            .docker
+            //
+            .export_image(reference)
+            //
+            .map_err(Into::into)
+            //
+            .boxed()
+    }
+
+    async fn import_image(&self, archive: BoxStream<'static, Bytes>) -> Result<(), EngineError> {
+        let mut stream = self
+            //
+            .docker
+            //
+            .import_image_stream(ImportImageOptions::default(), archive, None);
+
+        while let Some(message_result) = stream.next().await {
+            let BuildInfo { error, .. } = message_result?;
+
+            if let Some(error) = error {
+                return Err(EngineError::Failed(error));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_container(
+        &self,
+        name: Option<&str>,
+        config: container::Config<String>,
+    ) -> Result<String, EngineError> {
+        let options = name.map(|name| CreateContainerOptions {
+            //
+            name,
+            //
+            platform: None,
+        });
+
+        let ContainerCreateResponse {
+            //
+            id,
+            ..
+        } = self.docker.create_container(options, config).await?;
+
+        Ok(id)
+    }
+
+    async fn start_container(&self, id: &str) -> Result<(), EngineError> {
+        Ok(self
+            //
+            .docker
+            //
+            .start_container(id, None::<StartContainerOptions<String>>)
+            //
+            .await?)
+    }
+
+    async fn stop_container(&self, id: &str, timeout_seconds: i64) -> Result<(), EngineError> {
+        Ok(self
+            //
+            .docker
+            //
+            .stop_container(id, Some(StopContainerOptions { t: timeout_seconds }))
+            //
+            .await?)
+    }
+
+    async fn remove_container(&self, id: &str) -> Result<(), EngineError> {
+        let options = RemoveContainerOptions {
+            force: true,
+
+            ..Default::default()
+        };
+
+        Ok(self.docker.remove_container(id, Some(options)).await?)
+    }
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<(), EngineError> {
+        let options = RenameContainerOptions {
+            //
+            name,
+        };
+
+        Ok(self.docker.rename_container(id, options).await?)
+    }
+
+    async fn wait_container(&self, id: &str) -> Result<i64, EngineError> {
+        let mut stream = self
+            //
+            .docker
+            //
+            .wait_container(id, None::<WaitContainerOptions<String>>);
+
+        let mut result = 0;
+
+        while let Some(response_result) = stream.next().await {
+            result = match response_result {
+                Ok(ContainerWaitResponse { status_code, .. }) => status_code,
+
+                // bollard reports a non-zero exit code as an error
+                Err(DockerError::DockerContainerWaitError { code, .. }) => code,
+
+                Err(error) => return Err(error.into()),
+            };
+        }
+
+        Ok(result)
+    }
+
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, EngineError> {
+        Ok(self
+            //
+            .docker
+            //
+            .inspect_container(id, None::<InspectContainerOptions>)
+            //
+            .await?)
+    }
+
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>, EngineError> {
+        let mut filters = HashMap::default();
+
+        filters.insert("label".to_string(), vec![label.to_string()]);
+
+        let options = ListContainersOptions {
+            all: true,
+
+            limit: None,
+
+            size: false,
+
+            filters,
+        };
+
+        Ok(self.docker.list_containers(Some(options)).await?)
+    }
+
+    fn logs(&self, id: &str, lines: usize) -> BoxStream<'_, Result<LogOutput, EngineError>> {
+        let options = LogsOptions {
+            stdout: true,
+
+            stderr: true,
+
+            tail: lines.to_string(),
+
+            ..Default::default()
+        };
+
+        self
+            //
+            .docker
+            //
+            .logs(id, Some(options))
+            //
+            .map_err(Into::into)
+            //
+            .boxed()
+    }
+
+    async fn inspect_volume(&self, name: &str) -> Result<Option<Volume>, EngineError> {
+        match self.docker.inspect_volume(name).await {
+            Ok(volume) => Ok(Some(volume)),
+
+            Err(DockerError::DockerResponseServerError { status_code: 404, .. }) => Ok(None),
+
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn create_volume(&self, name: &str) -> Result<(), EngineError> {
+        let options = CreateVolumeOptions {
+            name,
+
+            ..Default::default()
+        };
+
+        self.docker.create_volume(options).await?;
+
+        Ok(())
+    }
+
+    async fn remove_volume(&self, name: &str) -> Result<(), EngineError> {
+        Ok(self.docker.remove_volume(name, None).await?)
+    }
+
+    async fn upload_to_container(
+        &self,
+        id: &str,
+        path: &str,
+        archive: BoxStream<'static, io::Result<Bytes>>,
+    ) -> Result<(), EngineError> {
+        let options = UploadToContainerOptions {
+            path,
+
+            ..Default::default()
+        };
+
+        // the API only takes chunks, so a read error ends the archive early and is reported once the engine is done
+        let read_error = Arc::new(Mutex::new(None));
+
+        let archive = archive
+            //
+            .scan(read_error.clone(), |read_error, chunk| {
+                let chunk = chunk
+                    //
+                    .map_err(|error| *read_error.lock().unwrap() = Some(error))
+                    //
+                    .ok();
+
+                future::ready(chunk)
+            })
+            //
+            .boxed();
+
+        let result = self
+            //
+            .docker
+            //
+            .upload_to_container_streaming(id, Some(options), archive)
+            //
+            .await;
+
+        if let Some(error) = read_error.lock().unwrap().take() {
+            return Err(EngineError::Archive(error));
+        }
+
+        Ok(result?)
+    }
+
+    fn download_from_container(&self, id: &str, path: &str) -> BoxStream<'_, Result<Bytes, EngineError>> {
+        let options = DownloadFromContainerOptions {
+            //
+            path: path.to_string(),
+        };
+
+        self
+            //
+            .docker
+            //
+            .download_from_container(id, Some(options))
+            //
+            .map_err(Into::into)
+            //
+            .boxed()
+    }
+}
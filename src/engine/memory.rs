@@ -0,0 +1,609 @@
+use crate::engine::Engine;
+use crate::error::EngineError;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+
+use bollard::container;
+use bollard::container::LogOutput;
+use bollard::models::*;
+use bollard::system::Version;
+
+use bytes::Bytes;
+
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+use futures_util::stream;
+use futures_util::stream::BoxStream;
+
+/// An engine that keeps images, containers and volumes in memory.
+///
+/// Pulls always succeed, containers with an entrypoint of their own (the CLI's helper containers) exit with code 0 as
+/// soon as they are started, and servers report healthy once running, unless [`Self::crash_on_start`] says otherwise.
+/// Exporting and importing images is not supported.
+#[derive(Default)]
+pub struct MemoryEngine {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Tags by image ID.
+    images: BTreeMap<String, Vec<String>>,
+
+    containers: BTreeMap<String, MemoryContainer>,
+
+    /// Archives uploaded into each volume, or bind-mounted directory, by source.
+    volumes: BTreeMap<String, Vec<u8>>,
+
+    /// Exit codes of the containers that crash as soon as they are started, by name.
+    crashes: BTreeMap<String, i64>,
+
+    next_id: u64,
+}
+
+struct MemoryContainer {
+    name: Option<String>,
+
+    image_id: String,
+
+    config: container::Config<String>,
+
+    status: ContainerStateStatusEnum,
+
+    exit_code: i64,
+
+    crashes: bool,
+}
+
+impl MemoryContainer {
+    /// Source of the data mount, which always comes first.
+    fn data_source(&self) -> Result<String, EngineError> {
+        self
+            //
+            .config
+            //
+            .host_config
+            //
+            .as_ref()
+            //
+            .and_then(|host_config| host_config.mounts.as_ref())
+            //
+            .and_then(|mounts| mounts.first())
+            //
+            .and_then(|mount| mount.source.clone())
+            //
+            .ok_or_else(|| EngineError::Failed("the container has no mounts".to_string()))
+    }
+}
+
+impl State {
+    fn next_id(&mut self) -> String {
+        self.next_id += 1;
+
+        format!("{:064x}", self.next_id)
+    }
+
+    fn check_name_is_free(&self, name: &str) -> Result<(), EngineError> {
+        let is_taken = self
+            //
+            .containers
+            //
+            .values()
+            //
+            .any(|container| container.name.as_deref() == Some(name));
+
+        if is_taken {
+            return Err(EngineError::Conflict(format!(
+                "the container name {name} is already in use"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Finds the ID of a container by its ID or, like Docker, by its name.
+    fn resolve(&self, id: &str) -> Result<String, EngineError> {
+        if self.containers.contains_key(id) {
+            return Ok(id.to_string());
+        }
+
+        self
+            //
+            .containers
+            //
+            .iter()
+            //
+            .find(|(_, container)| container.name.as_deref() == Some(id))
+            //
+            .map(|(id, _)| id.clone())
+            //
+            .ok_or_else(|| EngineError::NotFound(format!("no such container: {id}")))
+    }
+
+    fn container(&mut self, id: &str) -> Result<&mut MemoryContainer, EngineError> {
+        let id = self.resolve(id)?;
+
+        Ok(self.containers.get_mut(&id).unwrap())
+    }
+}
+
+impl MemoryEngine {
+    /// Makes the containers created as `name` from now on exit with `exit_code` as soon as they are started, e.g. to
+    /// see how the CLI deals with a server that does not come up.
+    pub fn crash_on_start(&self, name: &str, exit_code: i64) {
+        self
+            //
+            .state()
+            //
+            .crashes
+            //
+            .insert(name.to_string(), exit_code);
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Engine for MemoryEngine {
+    async fn version(&self) -> Result<Version, EngineError> {
+        Ok(Version {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+
+            api_version: Some("1.47".to_string()),
+
+            os: Some(std::env::consts::OS.to_string()),
+
+            arch: Some(std::env::consts::ARCH.to_string()),
+
+            ..Default::default()
+        })
+    }
+
+    async fn info(&self) -> Result<SystemInfo, EngineError> {
+        Ok(SystemInfo::default())
+    }
+
+    fn pull_image(&self, reference: &str) -> BoxStream<'_, Result<CreateImageInfo, EngineError>> {
+        let mut state = self.state();
+
+        let id = format!("sha256:{}", state.next_id());
+
+        state.images.insert(id, vec![reference.to_string()]);
+
+        let info = CreateImageInfo {
+            status: Some(format!("Downloaded newer image for {reference}")),
+
+            ..Default::default()
+        };
+
+        stream::iter([Ok(info)]).boxed()
+    }
+
+    async fn list_images(&self, reference: &str) -> Result<Vec<ImageSummary>, EngineError> {
+        let images = self
+            //
+            .state()
+            //
+            .images
+            //
+            .iter()
+            //
+            .filter(|(_, tags)| tags.iter().any(|tag| tag == reference))
+            //
+            .map(|(id, tags)| ImageSummary {
+                id: id.clone(),
+
+                repo_tags: tags.clone(),
+
+                ..Default::default()
+            })
+            //
+            .collect();
+
+        Ok(images)
+    }
+
+    async fn inspect_image(&self, id: &str) -> Result<ImageInspect, EngineError> {
+        let tags = self
+            //
+            .state()
+            //
+            .images
+            //
+            .get(id)
+            //
+            .cloned()
+            //
+            .ok_or_else(|| EngineError::NotFound(format!("no such image: {id}")))?;
+
+        Ok(ImageInspect {
+            id: Some(id.to_string()),
+
+            repo_tags: Some(tags),
+
+            ..Default::default()
+        })
+    }
+
+    fn export_image(&self, _reference: &str) -> BoxStream<'_, Result<Bytes, EngineError>> {
+        stream::iter([Err(EngineError::Unsupported("exporting images"))]).boxed()
+    }
+
+    async fn import_image(&self, _archive: BoxStream<'static, Bytes>) -> Result<(), EngineError> {
+        Err(EngineError::Unsupported("importing images"))
+    }
+
+    async fn create_container(
+        &self,
+        name: Option<&str>,
+        config: container::Config<String>,
+    ) -> Result<String, EngineError> {
+        let mut state = self.state();
+
+        if let Some(name) = name {
+            state.check_name_is_free(name)?;
+        }
+
+        let image = config
+            //
+            .image
+            //
+            .clone()
+            //
+            .ok_or_else(|| EngineError::Failed("the container has no image".to_string()))?;
+
+        // the CLI refers to images by ID, though a reference works as well
+        let image_id = state
+            //
+            .images
+            //
+            .iter()
+            //
+            .find(|(id, tags)| **id == image || tags.contains(&image))
+            //
+            .map(|(id, _)| id.clone())
+            //
+            .ok_or_else(|| EngineError::NotFound(format!("no such image: {image}")))?;
+
+        let crash = name.and_then(|name| state.crashes.get(name).copied());
+
+        let id = state.next_id();
+
+        state.containers.insert(
+            id.clone(),
+            MemoryContainer {
+                name: name.map(ToString::to_string),
+
+                image_id,
+
+                config,
+
+                status: ContainerStateStatusEnum::CREATED,
+
+                exit_code: crash.unwrap_or_default(),
+
+                crashes: crash.is_some(),
+            },
+        );
+
+        Ok(id)
+    }
+
+    async fn start_container(&self, id: &str) -> Result<(), EngineError> {
+        let mut state = self.state();
+
+        let container = state.container(id)?;
+
+        container.status = if container.crashes || container.config.entrypoint.is_some() {
+            ContainerStateStatusEnum::EXITED
+        } else {
+            ContainerStateStatusEnum::RUNNING
+        };
+
+        Ok(())
+    }
+
+    async fn stop_container(&self, id: &str, _timeout_seconds: i64) -> Result<(), EngineError> {
+        let mut state = self.state();
+
+        let container = state.container(id)?;
+
+        if let ContainerStateStatusEnum::RUNNING = container.status {
+            container.status = ContainerStateStatusEnum::EXITED;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_container(&self, id: &str) -> Result<(), EngineError> {
+        let mut state = self.state();
+
+        let id = state.resolve(id)?;
+
+        state.containers.remove(&id);
+
+        Ok(())
+    }
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<(), EngineError> {
+        let mut state = self.state();
+
+        state.check_name_is_free(name)?;
+
+        state.container(id)?.name = Some(name.to_string());
+
+        Ok(())
+    }
+
+    async fn wait_container(&self, id: &str) -> Result<i64, EngineError> {
+        let mut state = self.state();
+
+        let container = state.container(id)?;
+
+        if let ContainerStateStatusEnum::RUNNING = container.status {
+            return Err(EngineError::Failed(format!("the container {id} never exits")));
+        }
+
+        Ok(container.exit_code)
+    }
+
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, EngineError> {
+        let mut state = self.state();
+
+        let id = state.resolve(id)?;
+
+        let container = state.container(&id)?;
+
+        let health = match container.status {
+            ContainerStateStatusEnum::RUNNING if container.config.healthcheck.is_some() => Some(Health {
+                status: Some(HealthStatusEnum::HEALTHY),
+
+                ..Default::default()
+            }),
+
+            _ => None,
+        };
+
+        let mounts = container
+            //
+            .config
+            //
+            .host_config
+            //
+            .as_ref()
+            //
+            .and_then(|host_config| host_config.mounts.as_ref())
+            //
+            .map(|mounts| mounts.iter().map(mount_point).collect());
+
+        let config = &container.config;
+
+        Ok(ContainerInspectResponse {
+            id: Some(id),
+
+            name: container.name.as_ref().map(|name| format!("/{name}")),
+
+            image: Some(container.image_id.clone()),
+
+            config: Some(ContainerConfig {
+                image: config.image.clone(),
+
+                user: config.user.clone(),
+
+                env: config.env.clone(),
+
+                labels: config.labels.clone(),
+
+                ..Default::default()
+            }),
+
+            host_config: config.host_config.clone(),
+
+            mounts,
+
+            state: Some(ContainerState {
+                status: Some(container.status),
+
+                running: Some(container.status == ContainerStateStatusEnum::RUNNING),
+
+                health,
+
+                exit_code: Some(container.exit_code),
+
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        })
+    }
+
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>, EngineError> {
+        let summaries = self
+            //
+            .state()
+            //
+            .containers
+            //
+            .iter()
+            //
+            .filter(|(_, container)| {
+                container
+                    //
+                    .config
+                    //
+                    .labels
+                    //
+                    .as_ref()
+                    //
+                    .is_some_and(|labels| labels.contains_key(label))
+            })
+            //
+            .map(|(id, container)| {
+                let status = match container.status {
+                    ContainerStateStatusEnum::RUNNING if container.config.healthcheck.is_some() => {
+                        "Up 1 second (healthy)".to_string()
+                    }
+
+                    ContainerStateStatusEnum::RUNNING => "Up 1 second".to_string(),
+
+                    ContainerStateStatusEnum::CREATED => "Created".to_string(),
+
+                    _ => format!("Exited ({}) 1 second ago", container.exit_code),
+                };
+
+                ContainerSummary {
+                    id: Some(id.clone()),
+
+                    names: container.name.as_ref().map(|name| vec![format!("/{name}")]),
+
+                    image_id: Some(container.image_id.clone()),
+
+                    labels: container.config.labels.clone(),
+
+                    state: Some(container.status.to_string()),
+
+                    status: Some(status),
+
+                    ..Default::default()
+                }
+            })
+            //
+            .collect();
+
+        Ok(summaries)
+    }
+
+    fn logs(&self, _id: &str, _lines: usize) -> BoxStream<'_, Result<LogOutput, EngineError>> {
+        stream::empty().boxed()
+    }
+
+    async fn inspect_volume(&self, name: &str) -> Result<Option<Volume>, EngineError> {
+        let volume = self
+            //
+            .state()
+            //
+            .volumes
+            //
+            .contains_key(name)
+            //
+            .then(|| Volume {
+                name: name.to_string(),
+
+                driver: "local".to_string(),
+
+                labels: HashMap::default(),
+
+                ..Default::default()
+            });
+
+        Ok(volume)
+    }
+
+    async fn create_volume(&self, name: &str) -> Result<(), EngineError> {
+        self
+            //
+            .state()
+            //
+            .volumes
+            //
+            .entry(name.to_string())
+            //
+            .or_default();
+
+        Ok(())
+    }
+
+    async fn remove_volume(&self, name: &str) -> Result<(), EngineError> {
+        let mut state = self.state();
+
+        let is_in_use = state
+            //
+            .containers
+            //
+            .values()
+            //
+            .any(|container| container.data_source().is_ok_and(|source| source == name));
+
+        if is_in_use {
+            return Err(EngineError::Conflict(format!("the volume {name} is in use")));
+        }
+
+        state
+            //
+            .volumes
+            //
+            .remove(name)
+            //
+            .ok_or_else(|| EngineError::NotFound(format!("no such volume: {name}")))?;
+
+        Ok(())
+    }
+
+    async fn upload_to_container(
+        &self,
+        id: &str,
+        _path: &str,
+        archive: BoxStream<'static, io::Result<Bytes>>,
+    ) -> Result<(), EngineError> {
+        let source = self.state().container(id)?.data_source()?;
+
+        let chunks = archive
+            //
+            .try_collect::<Vec<_>>()
+            //
+            .await
+            //
+            .map_err(EngineError::Archive)?;
+
+        self
+            //
+            .state()
+            //
+            .volumes
+            //
+            .insert(source, chunks.concat());
+
+        Ok(())
+    }
+
+    fn download_from_container(&self, id: &str, _path: &str) -> BoxStream<'_, Result<Bytes, EngineError>> {
+        let mut state = self.state();
+
+        let result = state
+            //
+            .container(id)
+            //
+            .and_then(|container| container.data_source())
+            //
+            .map(|source| Bytes::from(state.volumes.get(&source).cloned().unwrap_or_default()));
+
+        stream::iter([result]).boxed()
+    }
+}
+
+/// Describes a mount the way inspecting a container does, where volumes are named rather than sourced.
+fn mount_point(mount: &Mount) -> MountPoint {
+    let is_volume = matches!(mount.typ, Some(MountTypeEnum::VOLUME));
+
+    MountPoint {
+        typ: mount.typ.map(|typ| match typ {
+            MountTypeEnum::VOLUME => MountPointTypeEnum::VOLUME,
+
+            _ => MountPointTypeEnum::BIND,
+        }),
+
+        name: mount.source.clone().filter(|_| is_volume),
+
+        source: mount.source.clone(),
+
+        destination: mount.target.clone(),
+
+        rw: Some(!mount.read_only.unwrap_or_default()),
+
+        ..Default::default()
+    }
+}
@@ -0,0 +1,182 @@
+//! The container engine behind the Docker API: Docker itself, or Podman through its Docker-compatible socket.
+//!
+//! Both run servers the same way, except for a few differences the CLI adapts to or points out:
+//!
+//! - Podman has no daemon to restart containers after a reboot; its `podman-restart` service does, but only for
+//!   containers with the `always` restart policy, which servers on Podman therefore get instead of `unless-stopped`.
+//! - Podman runs health checks through systemd timers, so they never run on hosts without systemd.
+//! - Rootless engines cannot listen on privileged ports with host networking, unless the host allows it.
+
+mod docker;
+mod memory;
+
+pub use docker::DockerEngine;
+pub use memory::MemoryEngine;
+
+use crate::error::EngineError;
+
+use std::fmt;
+use std::io;
+
+use bollard::container;
+use bollard::container::LogOutput;
+use bollard::models::*;
+use bollard::system::Version;
+
+use bytes::Bytes;
+
+use futures_util::stream::BoxStream;
+
+/// The operations of the Docker API that `Mayo` uses.
+///
+/// [`DockerEngine`] talks to a real daemon, [`MemoryEngine`] keeps everything in memory, for testing code built on
+/// `Mayo` without a daemon.
+// the CLI drives engines from a single thread, so their futures need not be `Send`
+#[allow(async_fn_in_trait)]
+pub trait Engine {
+    async fn version(&self) -> Result<Version, EngineError>;
+
+    async fn info(&self) -> Result<SystemInfo, EngineError>;
+
+    /// Pulls an image from its registry, reporting progress.
+    fn pull_image(&self, reference: &str) -> BoxStream<'_, Result<CreateImageInfo, EngineError>>;
+
+    /// Images tagged with `reference`.
+    async fn list_images(&self, reference: &str) -> Result<Vec<ImageSummary>, EngineError>;
+
+    async fn inspect_image(&self, id: &str) -> Result<ImageInspect, EngineError>;
+
+    /// An image as a tar archive, as `docker save` writes it.
+    fn export_image(&self, reference: &str) -> BoxStream<'_, Result<Bytes, EngineError>>;
+
+    /// Loads an image from a tar archive, as `docker load` does.
+    async fn import_image(&self, archive: BoxStream<'static, Bytes>) -> Result<(), EngineError>;
+
+    /// Creates a container and returns its id.
+    async fn create_container(
+        &self,
+        name: Option<&str>,
+        config: container::Config<String>,
+    ) -> Result<String, EngineError>;
+
+    async fn start_container(&self, id: &str) -> Result<(), EngineError>;
+
+    async fn stop_container(&self, id: &str, timeout_seconds: i64) -> Result<(), EngineError>;
+
+    /// Removes a container, stopping it first if it runs.
+    async fn remove_container(&self, id: &str) -> Result<(), EngineError>;
+
+    async fn rename_container(&self, id: &str, name: &str) -> Result<(), EngineError>;
+
+    /// Waits for a container to exit and returns its exit code.
+    async fn wait_container(&self, id: &str) -> Result<i64, EngineError>;
+
+    async fn inspect_container(&self, id: &str) -> Result<ContainerInspectResponse, EngineError>;
+
+    /// All containers with the label `label`, whether they run or not.
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>, EngineError>;
+
+    /// The last `lines` lines a container wrote to its standard output and error.
+    fn logs(&self, id: &str, lines: usize) -> BoxStream<'_, Result<LogOutput, EngineError>>;
+
+    /// A volume, or `None` if there is none with this name.
+    async fn inspect_volume(&self, name: &str) -> Result<Option<Volume>, EngineError>;
+
+    async fn create_volume(&self, name: &str) -> Result<(), EngineError>;
+
+    async fn remove_volume(&self, name: &str) -> Result<(), EngineError>;
+
+    /// Extracts a tar archive into a container's file system at `path`, failing if it cannot be read to the end.
+    async fn upload_to_container(
+        &self,
+        id: &str,
+        path: &str,
+        archive: BoxStream<'static, io::Result<Bytes>>,
+    ) -> Result<(), EngineError>;
+
+    /// A directory of a container's file system as a tar archive.
+    fn download_from_container(&self, id: &str, path: &str) -> BoxStream<'_, Result<Bytes, EngineError>>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EngineKind {
+    Docker,
+
+    Podman,
+}
+
+#[derive(Clone, Debug)]
+pub struct EngineInfo {
+    pub kind: EngineKind,
+
+    pub version: String,
+
+    pub api_version: String,
+
+    /// e.g. `linux/amd64`.
+    pub platform: String,
+
+    pub rootless: bool,
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Docker => f.write_str("Docker"),
+
+            Self::Podman => f.write_str("Podman"),
+        }
+    }
+}
+
+impl EngineInfo {
+    pub fn restart_policy(&self) -> RestartPolicyNameEnum {
+        match self.kind {
+            EngineKind::Docker => RestartPolicyNameEnum::UNLESS_STOPPED,
+
+            EngineKind::Podman => RestartPolicyNameEnum::ALWAYS,
+        }
+    }
+
+    /// Whether a server on this engine can listen on `port` with host networking.
+    ///
+    /// `unprivileged_port_start` is the host's `net.ipv4.ip_unprivileged_port_start`, when known.
+    pub fn can_listen_on(&self, port: u16, unprivileged_port_start: Option<u16>) -> bool {
+        !self.rootless || port >= unprivileged_port_start.unwrap_or(1024)
+    }
+
+    /// What to be aware of when running servers on this engine.
+    pub fn notes(&self) -> Vec<String> {
+        let mut result = Vec::new();
+
+        if self.kind == EngineKind::Podman {
+            let scope = if self.rootless { "--user " } else { "" };
+
+            result.push(format!(
+                "servers only start again after a reboot if podman-restart is enabled, e.g. with \
+                 `systemctl {scope}enable podman-restart.service`"
+            ));
+
+            result.push("health checks only run on hosts with systemd, otherwise servers stay `starting`".to_string());
+        }
+
+        if self.rootless {
+            result.push(
+                "servers cannot use ports below 1024, unless net.ipv4.ip_unprivileged_port_start allows it".to_string(),
+            );
+
+            // the CLI cannot tell which host UID that is, so it does not check the directories
+            let chown = match self.kind {
+                EngineKind::Docker => "e.g. from a root container that mounts it",
+
+                EngineKind::Podman => "e.g. with `podman unshare chown -R 1000:1000 <directory>`",
+            };
+
+            result.push(format!(
+                "the data directories of hardened servers must belong to the subordinate UID that 1000 maps to, {chown}"
+            ));
+        }
+
+        result
+    }
+}
@@ -0,0 +1,869 @@
+//! Errors of the library, one enum per failure domain.
+//!
+//! Functions return the error of their own domain; [`Error`] gathers them for the operations of
+//! [`Mayo`](crate::mayo::Mayo), which span several. Messages describe what failed, and the underlying error is kept as
+//! the source rather than repeated in the message, so [`describe`] or the caller's error reporter shows the whole
+//! chain.
+
+use crate::engine::EngineKind;
+
+use std::error::Error as StdError;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use semver::Version;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A request to the container engine failed.
+    #[error("{context}")]
+    Engine {
+        context: String,
+
+        #[source]
+        source: EngineError,
+    },
+
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+
+    #[error(transparent)]
+    Host(#[from] HostError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Parameters(#[from] ParametersError),
+
+    #[error(transparent)]
+    Server(#[from] ServerError),
+
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+
+    #[error(transparent)]
+    Adopt(#[from] AdoptError),
+
+    #[error(transparent)]
+    Fleet(#[from] FleetError),
+
+    #[error(transparent)]
+    Compose(#[from] ComposeError),
+
+    #[error(transparent)]
+    Schedule(#[from] ScheduleError),
+
+    #[error(transparent)]
+    Certificate(#[from] CertificateError),
+
+    #[error(transparent)]
+    Acme(#[from] AcmeError),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+impl Error {
+    /// Wraps an engine error with what was being done, e.g. `.map_err(Error::engine("failed to start the container"))`.
+    pub(crate) fn engine(context: impl Into<String>) -> impl FnOnce(EngineError) -> Self {
+        let context = context.into();
+
+        move |source| Self::Engine { context, source }
+    }
+}
+
+/// A failed file system operation on a known path.
+#[derive(Debug, thiserror::Error)]
+#[error("failed to {action} {}", path.display())]
+pub struct FileError {
+    pub action: &'static str,
+
+    pub path: PathBuf,
+
+    #[source]
+    pub source: io::Error,
+}
+
+impl FileError {
+    /// Wraps an I/O error with the operation and its path, e.g. `.map_err(FileError::on("read", &path))`.
+    pub(crate) fn on(action: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+
+        move |source| Self { action, path, source }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EngineError {
+    /// The settings to reach the engine are unusable, e.g. missing TLS files.
+    #[error("the connection settings of {host} are invalid")]
+    Connect {
+        host: String,
+
+        #[source]
+        source: bollard::errors::Error,
+    },
+
+    #[error("failed to open an SSH tunnel")]
+    Tunnel(#[source] HostError),
+
+    /// Nothing answers at the endpoint, e.g. because the daemon is not running.
+    #[error("the engine is unreachable, is it running?")]
+    Unreachable(#[source] bollard::errors::Error),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    /// A name is already taken, or an object is in use.
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("{0} is not supported by this engine")]
+    Unsupported(&'static str),
+
+    /// The engine accepted the request, then reported that it failed.
+    #[error("{0}")]
+    Failed(String),
+
+    /// An archive being uploaded could not be read to the end.
+    #[error("failed to read the archive")]
+    Archive(#[source] io::Error),
+
+    #[error(transparent)]
+    Api(bollard::errors::Error),
+}
+
+impl From<bollard::errors::Error> for EngineError {
+    fn from(error: bollard::errors::Error) -> Self {
+        use bollard::errors::Error::*;
+
+        match error {
+            DockerResponseServerError {
+                //
+                status_code: 404,
+                //
+                message,
+            } => Self::NotFound(message),
+
+            DockerResponseServerError {
+                //
+                status_code: 409,
+                //
+                message,
+            } => Self::Conflict(message),
+
+            IOError { .. } | HyperLegacyError { .. } | SocketNotFoundError(_) | RequestTimeoutError => {
+                Self::Unreachable(error)
+            }
+
+            error => Self::Api(error),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("failed to get a registry token")]
+    Token(#[source] reqwest::Error),
+
+    #[error("failed to get the tags of the app")]
+    Tags(#[source] reqwest::Error),
+
+    #[error("the registry has no version this CLI can run")]
+    NoCompatibleVersion,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HostError {
+    #[error("{0} is neither a host in the config file, a Docker context nor an endpoint")]
+    Unknown(String),
+
+    #[error("there is no Docker context named {0}")]
+    NoSuchContext(String),
+
+    #[error("the host {name} in the config file is invalid")]
+    InvalidConfigHost {
+        name: String,
+
+        #[source]
+        source: Box<HostError>,
+    },
+
+    #[error("the Docker context {name} is invalid")]
+    InvalidContext {
+        name: String,
+
+        #[source]
+        source: Box<HostError>,
+    },
+
+    #[error("DOCKER_HOST is invalid")]
+    InvalidDockerHost(#[source] Box<HostError>),
+
+    #[error("{0} is not a unix://, tcp://, https:// or ssh:// endpoint")]
+    UnsupportedEndpoint(String),
+
+    #[error("{0} has no host")]
+    MissingHost(String),
+
+    #[error("{0} is not a valid port")]
+    InvalidPort(String),
+
+    #[error("failed to find the home directory")]
+    NoHomeDirectory,
+
+    #[error("failed to parse {}", path.display())]
+    Parse {
+        path: PathBuf,
+
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error("failed to run ssh")]
+    Ssh(#[source] io::Error),
+
+    #[error("ssh to {destination} exited with {status}: {stderr}")]
+    SshExited {
+        destination: String,
+
+        status: ExitStatus,
+
+        stderr: String,
+    },
+
+    #[error("ssh to {destination} did not open a tunnel within {seconds} seconds")]
+    TunnelTimeout { destination: String, seconds: u64 },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to find the config directory")]
+    NoConfigDirectory,
+
+    #[error("failed to parse {}", path.display())]
+    Parse {
+        path: PathBuf,
+
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("failed to encode the config")]
+    Encode(#[source] toml::ser::Error),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParametersError {
+    #[error("the parameters are malformed")]
+    Malformed(#[from] serde_json::Error),
+
+    #[error("the schema of the parameters is missing or invalid")]
+    InvalidSchema,
+
+    #[error(
+        "the parameters are in schema {0}, which this CLI only reads up to {current}; please upgrade the CLI",
+        current = crate::labels::CURRENT_SCHEMA
+    )]
+    NewerSchema(u32),
+
+    #[error("server names may only contain lowercase letters, digits and dashes")]
+    InvalidName(String),
+
+    #[error("library names may only contain letters, digits, dashes and underscores")]
+    InvalidLibraryName(String),
+
+    #[error("{} is not an absolute path", .0.display())]
+    NotAbsolute(PathBuf),
+
+    #[error("{} is not a directory", .0.display())]
+    NotADirectory(PathBuf),
+
+    #[error(
+        "{} is not writable by the hardened profile's user, try `chown -R {user} {}`",
+        path.display(),
+        path.display()
+    )]
+    NotWritable { path: PathBuf, user: &'static str },
+
+    #[error("failed to read the library {name} at {}", path.display())]
+    UnreadableLibrary {
+        name: String,
+
+        path: PathBuf,
+
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{} is not readable by the hardened profile's user, try `chmod o+rx {}`", .0.display(), .0.display())]
+    NotReadable(PathBuf),
+
+    #[error("there already is a library named {0}")]
+    LibraryExists(String),
+
+    #[error("there is no library named {0}")]
+    NoSuchLibrary(String),
+
+    #[error("{0} is not a KEY=VALUE pair")]
+    InvalidPair(String),
+
+    #[error("{0} is not a valid variable name")]
+    InvalidVariableName(String),
+
+    #[error("{0} is managed by the CLI and cannot be set directly")]
+    ReservedVariable(String),
+
+    #[error("the secret {0} has no value")]
+    SecretWithoutValue(String),
+
+    #[error("invalid line {line} in {}", path.display())]
+    InvalidLine {
+        line: usize,
+
+        path: PathBuf,
+
+        #[source]
+        source: Box<ParametersError>,
+    },
+
+    #[error("failed to inquire the {what}")]
+    Prompt {
+        what: &'static str,
+
+        #[source]
+        source: inquire::InquireError,
+    },
+
+    #[error("failed to get the latest compatible version")]
+    LatestVersion(#[source] RegistryError),
+
+    #[error("{context}")]
+    Certificate {
+        context: &'static str,
+
+        #[source]
+        source: CertificateError,
+    },
+
+    #[error(transparent)]
+    Acme(#[from] AcmeError),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+impl ParametersError {
+    pub(crate) fn prompt(what: &'static str) -> impl FnOnce(inquire::InquireError) -> Self {
+        move |source| Self::Prompt { what, source }
+    }
+
+    pub(crate) fn certificate(context: &'static str) -> impl FnOnce(CertificateError) -> Self {
+        move |source| Self::Certificate { context, source }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    /// A field [`Server`](crate::server::Server) is built from is missing from the container summary.
+    #[error("the container has no {0}")]
+    MissingField(&'static str),
+
+    #[error("the container has no {0} label")]
+    MissingLabel(&'static str),
+
+    #[error("the {label} label of the container is malformed")]
+    MalformedLabel {
+        label: &'static str,
+
+        #[source]
+        source: ParametersError,
+    },
+
+    #[error("there already is a server named {0}")]
+    AlreadyExists(String),
+
+    #[error("the volume {0} already exists")]
+    VolumeExists(String),
+
+    #[error("{} is not empty", .0.display())]
+    DirectoryNotEmpty(PathBuf),
+
+    #[error(
+        "{name} was written in schema {schema} by a newer CLI and is read-only; please upgrade the CLI to change it"
+    )]
+    ReadOnly { name: String, schema: u32 },
+
+    #[error("a server cannot be renamed by replacing it")]
+    Renamed,
+
+    /// Secrets that were left out of the parameters, e.g. of a server started from a compose file, have no value.
+    #[error("the values of {} are missing", .0.join(", "))]
+    MissingSecrets(Vec<String>),
+
+    #[error("the data directory cannot be used")]
+    UnusableDataDirectory(#[source] ParametersError),
+
+    #[error("a library cannot be used")]
+    UnusableLibrary(#[source] ParametersError),
+
+    #[error("a rootless {kind} cannot listen on port {port}, choose a port of 1024 or above")]
+    PrivilegedPort { kind: EngineKind, port: u16 },
+
+    #[error("failed to prepare the data volume")]
+    DataVolume(#[source] Box<Error>),
+
+    #[error("the image {0} is still missing after copying it")]
+    ImageMissing(String),
+
+    /// A short-lived helper container, e.g. the one preparing a data volume, failed.
+    #[error("the container exited with code {0}")]
+    HelperExited(i64),
+
+    #[error("the server exited with code {0}")]
+    Exited(i64),
+
+    #[error("the server is unhealthy")]
+    Unhealthy,
+
+    #[error("the server did not become healthy within {0} seconds")]
+    Timeout(u64),
+
+    #[error("the server is not ready, its last log lines were:\n{}", logs.join("\n"))]
+    NotReady {
+        logs: Vec<String>,
+
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The data of a restore was unpacked, but could not be moved into the server's data mount.
+    #[error("failed to move the restored data into place, it is kept in the volume {volume}")]
+    Staged {
+        volume: String,
+
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The replacement of a server failed, and the previous container runs again.
+    #[error("the previous container has been restored")]
+    Restored(#[source] Box<Error>),
+
+    /// The replacement of a server failed, and the previous container could not be started again either.
+    #[error("the previous container {id} could not be restarted ({}), start it by hand", describe(.restart))]
+    NotRestored {
+        id: String,
+
+        restart: Box<Error>,
+
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The new container of a server runs, but still under its temporary name.
+    #[error("the new container runs as {from}, rename it to {to} by hand")]
+    NotRenamed {
+        from: String,
+
+        to: String,
+
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// A container cannot be taken over as a server.
+#[derive(Debug, thiserror::Error)]
+pub enum AdoptError {
+    #[error("{0} already is a server")]
+    AlreadyManaged(String),
+
+    #[error("the container has no {0}")]
+    MissingField(&'static str),
+
+    #[error("the image {0} is not the app's")]
+    NotAnApp(String),
+
+    #[error("the image {0} is not tagged with a compatible app version")]
+    IncompatibleVersion(String),
+
+    #[error("{name} cannot be used as a server name, choose another one")]
+    InvalidName {
+        name: String,
+
+        #[source]
+        source: ParametersError,
+    },
+
+    #[error("the container has no BUN_PORT, so its port is unknown")]
+    MissingPort,
+
+    #[error("BUN_PORT={0} is not a port")]
+    InvalidPort(String),
+}
+
+/// A servers manifest cannot be read, or describes a server that cannot exist.
+#[derive(Debug, thiserror::Error)]
+pub enum FleetError {
+    #[error("failed to parse {}", path.display())]
+    Parse {
+        path: PathBuf,
+
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("the entry of {name} is invalid")]
+    InvalidEntry {
+        name: String,
+
+        #[source]
+        source: ParametersError,
+    },
+
+    #[error("the certificate of {name} cannot be used")]
+    Certificate {
+        name: String,
+
+        #[source]
+        source: CertificateError,
+    },
+
+    #[error("failed to compare the parameters")]
+    Encode(#[source] serde_json::Error),
+
+    #[error("failed to get the latest compatible version")]
+    LatestVersion(#[source] RegistryError),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ComposeError {
+    #[error("failed to parse {}", path.display())]
+    Parse {
+        path: PathBuf,
+
+        #[source]
+        source: serde_yaml_ng::Error,
+    },
+
+    #[error("failed to encode the compose file")]
+    Encode(#[source] serde_yaml_ng::Error),
+
+    #[error("{} has no service of the app", .0.display())]
+    NoServer(PathBuf),
+
+    #[error("several services are servers: {}, choose one", .0.join(", "))]
+    SeveralServers(Vec<String>),
+
+    #[error("the parameters label of {service} is malformed")]
+    MalformedLabel {
+        service: String,
+
+        #[source]
+        source: ParametersError,
+    },
+
+    #[error("the environment of the service is invalid")]
+    InvalidVariable(#[source] ParametersError),
+
+    /// A service without a parameters label that cannot be mapped to a server either.
+    #[error("{service} cannot be mapped to a server")]
+    Unmappable {
+        service: String,
+
+        #[source]
+        source: AdoptError,
+    },
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("failed to format the current time")]
+    Timestamp(#[source] time::error::Format),
+
+    #[error("failed to encode the manifest")]
+    EncodeManifest(#[source] serde_json::Error),
+
+    #[error("failed to parse the manifest")]
+    ParseManifest(#[source] serde_json::Error),
+
+    #[error("the archive has no {0}")]
+    MissingEntry(&'static str),
+
+    #[error("expected {expected} in the archive, found {found}")]
+    UnexpectedEntry { expected: &'static str, found: String },
+
+    #[error("the archive is in format {0}, but this CLI only reads formats up to {max}; please upgrade the CLI", max = crate::backup::FORMAT_VERSION)]
+    UnknownFormat(u32),
+
+    #[error(
+        "the archive was written by CLI {0}, whose server parameters this CLI {current} cannot read",
+        current = crate::versioning::current_cli_version()
+    )]
+    IncompatibleCli(Version),
+
+    #[error(
+        "the archive holds data of app {0}, which this CLI cannot run; restore it with a CLI release that supports \
+         that app version"
+    )]
+    IncompatibleApp(Version),
+
+    /// Reading or writing the archive failed.
+    #[error("{context}")]
+    Io {
+        context: String,
+
+        #[source]
+        source: io::Error,
+    },
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+impl BackupError {
+    pub(crate) fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let context = context.into();
+
+        move |source| Self::Io { context, source }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("{cron} is not a valid cron expression")]
+    InvalidCron {
+        cron: String,
+
+        #[source]
+        source: croner::errors::CronError,
+    },
+
+    #[error("failed to find the next backup time")]
+    NextOccurrence(#[source] croner::errors::CronError),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    /// The certificate or the key cannot be used, for the reason given.
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("{context}")]
+    Parse {
+        context: String,
+
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    #[error("{context}")]
+    Generate {
+        context: &'static str,
+
+        #[source]
+        source: rcgen::Error,
+    },
+
+    #[error("failed to find the data directory")]
+    NoDataDirectory,
+
+    #[error("failed to load the local CA")]
+    LocalCa(#[source] Box<CertificateError>),
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+impl CertificateError {
+    pub(crate) fn parse<E>(context: impl Into<String>) -> impl FnOnce(E) -> Self
+    where
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let context = context.into();
+
+        move |source| Self::Parse {
+            context,
+
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn generate(context: &'static str) -> impl FnOnce(rcgen::Error) -> Self {
+        move |source| Self::Generate { context, source }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AcmeError {
+    #[error("failed to inquire the {what}")]
+    Prompt {
+        what: &'static str,
+
+        #[source]
+        source: inquire::InquireError,
+    },
+
+    #[error("{context}")]
+    Http {
+        context: &'static str,
+
+        #[source]
+        source: reqwest::Error,
+    },
+
+    #[error("not a valid list of domains")]
+    Domains(#[source] CertificateError),
+
+    /// The certificate authority answered with a problem document.
+    #[error("the certificate authority refused the request: {0}")]
+    Refused(String),
+
+    /// The certificate authority answered in a way the CLI cannot follow.
+    #[error("{0}")]
+    Protocol(String),
+
+    #[error("the order is invalid: {0}")]
+    InvalidOrder(String),
+
+    #[error("the authorization is {status}: {problem}")]
+    FailedAuthorization { status: String, problem: String },
+
+    #[error("the {0} did not become valid in time")]
+    Timeout(&'static str),
+
+    #[error("failed to listen on port {port} for the HTTP-01 challenge; free it, or use the DNS-01 challenge")]
+    Listen {
+        port: u16,
+
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to run {}", .hook.display())]
+    Hook {
+        hook: PathBuf,
+
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{} {action} exited with {status}", hook.display())]
+    HookFailed {
+        hook: PathBuf,
+
+        action: String,
+
+        status: ExitStatus,
+    },
+
+    #[error("{context}")]
+    Key {
+        context: &'static str,
+
+        #[source]
+        source: rcgen::Error,
+    },
+
+    #[error("the account key is not a P-256 key: {0}")]
+    KeyType(String),
+
+    #[error("failed to find the data directory")]
+    NoDataDirectory,
+
+    /// A step of obtaining the certificate failed.
+    #[error("{context}")]
+    Step {
+        context: String,
+
+        #[source]
+        source: Box<AcmeError>,
+    },
+
+    #[error(transparent)]
+    File(#[from] FileError),
+}
+
+impl AcmeError {
+    pub(crate) fn prompt(what: &'static str) -> impl FnOnce(inquire::InquireError) -> Self {
+        move |source| Self::Prompt { what, source }
+    }
+
+    pub(crate) fn http(context: &'static str) -> impl FnOnce(reqwest::Error) -> Self {
+        move |source| Self::Http { context, source }
+    }
+
+    pub(crate) fn key(context: &'static str) -> impl FnOnce(rcgen::Error) -> Self {
+        move |source| Self::Key { context, source }
+    }
+
+    pub(crate) fn step(context: impl Into<String>) -> impl FnOnce(Self) -> Self {
+        let context = context.into();
+
+        move |source| Self::Step {
+            context,
+
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Something that went wrong without failing the operation, e.g. a leftover to remove by hand.
+///
+/// Operations hand them to the caller, e.g. through [`Mayo::on_warning`](crate::mayo::Mayo::on_warning), rather than
+/// printing them.
+#[derive(Debug, thiserror::Error)]
+pub enum Warning {
+    #[error("failed to remove the container {id} ({}), remove it by hand", describe(.error))]
+    ContainerLeft { id: String, error: Box<Error> },
+
+    #[error("failed to remove the volume {volume} ({}), remove it by hand", describe(.error))]
+    VolumeLeft { volume: String, error: Box<Error> },
+
+    #[error("failed to pull {reference} ({}), copying it instead", describe(.error))]
+    PullFailed { reference: String, error: Box<Error> },
+
+    #[error("failed to clean up the record {record} ({}), remove it by hand", describe(.error))]
+    RecordLeft { record: String, error: AcmeError },
+
+    /// The certificate can be used, but some clients may not accept it, e.g. because its chain is incomplete.
+    #[error("{0}")]
+    Certificate(String),
+}
+
+/// Formats an error with its sources, e.g. "failed to get the image: the engine is unreachable, is it running?".
+pub fn describe(error: &dyn StdError) -> String {
+    let mut result = error.to_string();
+
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        result.push_str(": ");
+
+        result.push_str(&error.to_string());
+
+        source = error.source();
+    }
+
+    result
+}
@@ -0,0 +1,542 @@
+//! Servers described in a manifest file, and the changes that make a host match it.
+//!
+//! The manifest is a TOML file with one table per server, e.g.
+//!
+//! ```toml
+//! [servers.music]
+//! port = 8443
+//! tls = { crt = "certs/music.crt", key = "certs/music.key" }
+//! security = "hardened"
+//! data = "/srv/mayo/music"
+//! libraries = { jazz = "/srv/music/jazz" }
+//! environment = { TZ = "Europe/Paris" }
+//! secrets = ["LASTFM_API_KEY"]
+//! env_files = ["music.env"]
+//! ```
+//!
+//! Only `port` is required. Without `security`, a server is hardened, as when it is created interactively. Without
+//! `version`, a server keeps the version it runs, and a new one gets the latest
+//! compatible version; without `data`, its data lives in a volume. Relative paths are relative to the manifest.
+//!
+//! Without `tls`, a server whose certificate is obtained over ACME keeps it and goes on renewing it, while any other
+//! server has TLS disabled. With `tls`, the files replace the certificate and it is no longer renewed.
+
+use crate::certificate;
+use crate::engine::Engine;
+use crate::error::FileError;
+use crate::error::FleetError;
+use crate::error::Result;
+use crate::mayo::Mayo;
+use crate::parameters::Authentication;
+use crate::parameters::Environment;
+use crate::parameters::Libraries;
+use crate::parameters::Library;
+use crate::parameters::Name;
+use crate::parameters::Parameters;
+use crate::parameters::Port;
+use crate::parameters::Security;
+use crate::parameters::Storage;
+use crate::parameters::Tls;
+use crate::server::Server;
+use crate::versioning;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use semver::Version;
+
+use serde::Deserialize;
+
+/// The parsed manifest, with paths already resolved against its directory.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fleet {
+    #[serde(default)]
+    pub servers: BTreeMap<String, Entry>,
+}
+
+/// A server as described in the manifest.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub version: Option<Version>,
+
+    pub port: u16,
+
+    #[serde(default = "default_authentication")]
+    pub authentication: bool,
+
+    /// Left out for servers whose certificate is obtained over ACME, which is not described in the manifest.
+    pub tls: Option<TlsFiles>,
+
+    #[serde(default = "default_security")]
+    pub security: Security,
+
+    /// A directory on the host, instead of a volume.
+    pub data: Option<PathBuf>,
+
+    #[serde(default)]
+    pub libraries: BTreeMap<String, PathBuf>,
+
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub env_files: Vec<PathBuf>,
+
+    /// Keys of `environment` or `env_files` whose values are secret.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsFiles {
+    pub crt: PathBuf,
+
+    pub key: PathBuf,
+}
+
+fn default_authentication() -> bool {
+    true
+}
+
+/// New servers are hardened, as when created interactively; [`Security::default`] describes servers created before
+/// security profiles existed.
+fn default_security() -> Security {
+    Security::Hardened
+}
+
+impl Fleet {
+    pub fn load(path: &Path) -> Result<Self, FleetError> {
+        let contents = fs::read_to_string(path)
+            //
+            .map_err(FileError::on("read", path))?;
+
+        let mut fleet = toml::from_str::<Self>(&contents)
+            //
+            .map_err(|source| FleetError::Parse {
+                path: path.to_path_buf(),
+
+                source,
+            })?;
+
+        let directory = path
+            //
+            .parent()
+            //
+            .unwrap_or(Path::new(""));
+
+        for entry in fleet.servers.values_mut() {
+            entry.resolve_paths(directory);
+        }
+
+        Ok(fleet)
+    }
+}
+
+impl Entry {
+    fn resolve_paths(&mut self, directory: &Path) {
+        // joining an absolute path replaces the directory
+        let resolve = |path: &mut PathBuf| *path = directory.join(&*path);
+
+        if let Some(TlsFiles { crt, key }) = &mut self.tls {
+            resolve(crt);
+
+            resolve(key);
+        }
+
+        if let Some(data) = &mut self.data {
+            resolve(data);
+        }
+
+        self.libraries.values_mut().for_each(resolve);
+
+        self.env_files.iter_mut().for_each(resolve);
+    }
+
+    /// The parameters of the server named `name`, in `version` unless the entry pins one.
+    pub fn to_parameters(&self, name: &str, version: Version) -> Result<Parameters, FleetError> {
+        let invalid = |source| FleetError::InvalidEntry {
+            name: name.to_string(),
+
+            source,
+        };
+
+        let tls = match &self.tls {
+            Some(TlsFiles { crt, key }) => {
+                let crt = fs::read_to_string(crt)
+                    //
+                    .map_err(FileError::on("read", crt))?;
+
+                let key = fs::read_to_string(key)
+                    //
+                    .map_err(FileError::on("read", key))?;
+
+                certificate::check_pair(&crt, &key)
+                    //
+                    .map_err(|source| FleetError::Certificate {
+                        name: name.to_string(),
+
+                        source,
+                    })?;
+
+                Tls::new(crt, key)
+            }
+
+            None => Tls::disabled(),
+        };
+
+        let storage = match &self.data {
+            Some(path) => Storage::Bind(path.clone()),
+
+            None => Storage::Volume,
+        };
+
+        let mut libraries = Libraries::default();
+
+        for (library_name, path) in &self.libraries {
+            Library::try_new(library_name.clone(), path.clone())
+                //
+                .and_then(|library| libraries.add(library))
+                //
+                .map_err(invalid)?;
+        }
+
+        let pairs = self
+            //
+            .environment
+            //
+            .iter()
+            //
+            .map(|(key, value)| format!("{key}={value}"))
+            //
+            .collect::<Vec<_>>();
+
+        let environment = Environment::try_new(&pairs, &self.env_files, &self.secrets)
+            //
+            .map_err(invalid)?;
+
+        Ok(Parameters {
+            name: Name::try_new(name.to_string()).map_err(invalid)?,
+
+            version: self.version.clone().unwrap_or(version),
+
+            port: Port::new(self.port),
+
+            authentication: Authentication::new(self.authentication),
+
+            tls,
+
+            security: self.security,
+
+            storage,
+
+            libraries,
+
+            environment,
+
+            acme: None,
+        })
+    }
+}
+
+/// A change to a host, in the order [`plan`] lists them.
+#[derive(Clone, Debug)]
+pub enum Change {
+    /// A server that is not in the manifest, removed with `prune`. Its data is kept.
+    Remove(Server),
+
+    /// A server whose parameters differ from its entry, by the given fields.
+    Replace {
+        server: Box<Server>,
+
+        parameters: Parameters,
+
+        fields: Vec<String>,
+    },
+
+    /// An entry without a server.
+    Create(Parameters),
+}
+
+impl Change {
+    pub fn name(&self) -> &Name {
+        match self {
+            Self::Remove(server) => &server.parameters.name,
+
+            Self::Replace { server, .. } => &server.parameters.name,
+
+            Self::Create(parameters) => &parameters.name,
+        }
+    }
+}
+
+/// What it takes for a host to match a manifest.
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+
+    /// Servers that already match their entry.
+    pub unchanged: Vec<String>,
+
+    /// Servers of a newer CLI, which are left alone whatever their entry says.
+    pub read_only: Vec<String>,
+
+    /// Servers that are not in the manifest and are kept, since pruning was not asked for.
+    pub unlisted: Vec<String>,
+}
+
+/// Compares the servers of a host with a manifest, removing the unlisted ones only if `prune` is set.
+pub async fn plan(fleet: &Fleet, servers: Vec<Server>, prune: bool) -> Result<Plan> {
+    let mut plan = Plan::default();
+
+    let mut servers = servers
+        //
+        .into_iter()
+        //
+        .map(|server| (server.parameters.name.to_string(), server))
+        //
+        .collect::<BTreeMap<_, _>>();
+
+    let mut removals = Vec::new();
+
+    let mut replacements = Vec::new();
+
+    let mut creations = Vec::new();
+
+    // only fetched if an entry needs it
+    let mut latest_version = None;
+
+    for (name, entry) in &fleet.servers {
+        match servers.remove(name) {
+            Some(server) if server.is_read_only() => {
+                plan.read_only.push(name.clone());
+            }
+
+            Some(server) => {
+                let mut parameters = entry.to_parameters(name, server.parameters.version.clone())?;
+
+                // a renewed certificate would differ from any file, so it is only replaced if the entry asks to
+                if entry.tls.is_none() && server.parameters.acme.is_some() {
+                    parameters.tls = server.parameters.tls.clone();
+                }
+
+                let fields = changed_fields(&server.parameters, &parameters)?;
+
+                if fields.is_empty() {
+                    plan.unchanged.push(name.clone());
+
+                    continue;
+                }
+
+                // a certificate that is not changed keeps being renewed
+                if !fields.iter().any(|field| field == "tls") {
+                    parameters.acme = server.parameters.acme.clone();
+                }
+
+                replacements.push(Change::Replace {
+                    server: Box::new(server),
+
+                    parameters,
+
+                    fields,
+                });
+            }
+
+            None => {
+                let version = match (&entry.version, &latest_version) {
+                    (Some(version), _) | (None, Some(version)) => version.clone(),
+
+                    (None, None) => {
+                        let version = versioning::get_latest_compatible_app_version()
+                            //
+                            .await
+                            //
+                            .map_err(FleetError::LatestVersion)?;
+
+                        latest_version.insert(version).clone()
+                    }
+                };
+
+                creations.push(Change::Create(entry.to_parameters(name, version)?));
+            }
+        }
+    }
+
+    for (name, server) in servers {
+        if prune {
+            removals.push(Change::Remove(server));
+        } else {
+            plan.unlisted.push(name);
+        }
+    }
+
+    // removals first, so that their ports and names are free for the others
+    plan.changes = removals
+        //
+        .into_iter()
+        //
+        .chain(replacements)
+        //
+        .chain(creations)
+        //
+        .collect();
+
+    Ok(plan)
+}
+
+/// The top-level fields of the parameters that differ, e.g. `port` or `environment`.
+///
+/// How a certificate was obtained is not part of the manifest, so `acme` is left out.
+fn changed_fields(actual: &Parameters, desired: &Parameters) -> Result<Vec<String>, FleetError> {
+    let actual = serde_json::to_value(actual).map_err(FleetError::Encode)?;
+
+    let desired = serde_json::to_value(desired).map_err(FleetError::Encode)?;
+
+    let (Some(actual), Some(desired)) = (actual.as_object(), desired.as_object()) else {
+        return Ok(Vec::new());
+    };
+
+    let fields = desired
+        //
+        .iter()
+        //
+        .filter(|(key, _)| *key != "acme")
+        //
+        .filter(|(key, value)| actual.get(*key) != Some(*value))
+        //
+        .map(|(key, _)| key.clone())
+        //
+        .collect();
+
+    Ok(fields)
+}
+
+/// Makes one change of a plan, waiting for created and replaced servers to become ready.
+///
+/// Returns the ID of the new container, if there is one.
+pub async fn apply<E: Engine>(mayo: &Mayo<E>, change: Change, timeout: Duration) -> Result<Option<String>> {
+    match change {
+        Change::Remove(server) => {
+            mayo.remove_server(&server, false)
+                //
+                .await?;
+
+            Ok(None)
+        }
+
+        Change::Replace { server, parameters, .. } => {
+            let id = mayo
+                //
+                .replace_server(&server, parameters, timeout)
+                //
+                .await?;
+
+            Ok(Some(id))
+        }
+
+        Change::Create(parameters) => {
+            let id = mayo
+                //
+                .create_server(parameters, None)
+                //
+                .await?;
+
+            mayo.wait_until_ready(&id, timeout)
+                //
+                .await?;
+
+            Ok(Some(id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::labels::CURRENT_SCHEMA;
+
+    fn fleet(manifest: &str) -> Fleet {
+        toml::from_str(manifest).unwrap()
+    }
+
+    fn server(parameters: Parameters) -> Server {
+        Server {
+            id: "id".to_string(),
+
+            state: "running".to_string(),
+
+            health: None,
+
+            parameters,
+
+            schema: CURRENT_SCHEMA,
+
+            origin: None,
+        }
+    }
+
+    const MANIFEST: &str = r#"
+        [servers.music]
+        version = "1.0.0"
+        port = 8443
+    "#;
+
+    #[tokio::test]
+    async fn an_entry_without_security_creates_a_hardened_server() {
+        let plan = plan(&fleet(MANIFEST), Vec::new(), false).await.unwrap();
+
+        let [Change::Create(parameters)] = plan.changes.as_slice() else {
+            panic!("expected a single creation, got {:?}", plan.changes);
+        };
+
+        assert!(parameters.security.is_hardened());
+    }
+
+    #[tokio::test]
+    async fn an_entry_without_security_keeps_a_hardened_server() {
+        let fleet = fleet(MANIFEST);
+
+        let mut parameters = fleet.servers["music"]
+            //
+            .to_parameters("music", Version::new(1, 0, 0))
+            //
+            .unwrap();
+
+        parameters.security = Security::Hardened;
+
+        let plan = plan(&fleet, vec![server(parameters)], false).await.unwrap();
+
+        assert!(plan.changes.is_empty(), "expected no changes, got {:?}", plan.changes);
+
+        assert_eq!(plan.unchanged, ["music"]);
+    }
+
+    #[tokio::test]
+    async fn an_entry_without_security_hardens_a_standard_server() {
+        let fleet = fleet(MANIFEST);
+
+        let mut parameters = fleet.servers["music"]
+            //
+            .to_parameters("music", Version::new(1, 0, 0))
+            //
+            .unwrap();
+
+        parameters.security = Security::Standard;
+
+        let plan = plan(&fleet, vec![server(parameters)], false).await.unwrap();
+
+        let [Change::Replace { parameters, fields, .. }] = plan.changes.as_slice() else {
+            panic!("expected a single replacement, got {:?}", plan.changes);
+        };
+
+        assert_eq!(fields, &["security"]);
+
+        assert!(parameters.security.is_hardened());
+    }
+}
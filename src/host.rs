@@ -0,0 +1,483 @@
+//! Which Docker daemon to talk to.
+//!
+//! A host is chosen with `--host`, which takes a host named in the config file, a Docker context, or an endpoint such
+//! as `unix:///var/run/docker.sock`, `tcp://10.0.0.2:2376` or `ssh://admin@media-1`. Without `--host`, the CLI
+//! follows the Docker CLI: `DOCKER_HOST`, then `DOCKER_CONTEXT`, then the current context, then the local socket of
+//! Docker or, failing that, of Podman.
+
+use crate::config::Config;
+use crate::error::HostError;
+
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+pub const DEFAULT_SOCKET: &str = "unix:///var/run/docker.sock";
+
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A host in the config file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HostEntry {
+    /// e.g. `tcp://10.0.0.2:2376` or `ssh://admin@media-1`.
+    pub endpoint: String,
+
+    /// Directory with the `ca.pem`, `cert.pem` and `key.pem` of a TCP endpoint that requires TLS client
+    /// certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Host {
+    /// How the host was chosen, for display.
+    pub name: String,
+
+    pub endpoint: Endpoint,
+}
+
+#[derive(Clone, Debug)]
+pub enum Endpoint {
+    Unix(String),
+
+    Tcp {
+        address: String,
+
+        tls: Option<TlsFiles>,
+    },
+
+    /// Docker's socket on another machine, reached through an SSH tunnel.
+    Ssh {
+        /// e.g. `admin@media-1`.
+        destination: String,
+
+        port: Option<u16>,
+
+        /// Path of the daemon's socket on the remote machine.
+        socket: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    pub ca: PathBuf,
+
+    pub cert: PathBuf,
+
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    fn in_directory(directory: PathBuf) -> Self {
+        Self {
+            ca: directory.join("ca.pem"),
+
+            cert: directory.join("cert.pem"),
+
+            key: directory.join("key.pem"),
+        }
+    }
+}
+
+impl Host {
+    /// Resolves `--host`, or the Docker CLI's defaults without it.
+    pub fn resolve(selector: Option<&str>) -> Result<Self, HostError> {
+        match selector {
+            Some(selector) => Self::resolve_selector(selector),
+
+            None => Self::resolve_default(),
+        }
+    }
+
+    fn resolve_selector(selector: &str) -> Result<Self, HostError> {
+        if selector.contains("://") {
+            return Ok(Self {
+                name: selector.to_string(),
+
+                endpoint: Endpoint::parse(selector, env_tls_files())?,
+            });
+        }
+
+        let config = Config::load()?;
+
+        if let Some(HostEntry { endpoint, cert_path }) = config.hosts.get(selector) {
+            let tls = cert_path.clone().map(TlsFiles::in_directory);
+
+            return Ok(Self {
+                name: selector.to_string(),
+
+                endpoint: Endpoint::parse(endpoint, tls)
+                    //
+                    .map_err(|source| HostError::InvalidConfigHost {
+                        name: selector.to_string(),
+
+                        source: Box::new(source),
+                    })?,
+            });
+        }
+
+        match context(selector)? {
+            Some(endpoint) => Ok(Self {
+                name: selector.to_string(),
+
+                endpoint,
+            }),
+
+            None => Err(HostError::Unknown(selector.to_string())),
+        }
+    }
+
+    fn resolve_default() -> Result<Self, HostError> {
+        if let Ok(docker_host) = env::var("DOCKER_HOST") {
+            return Ok(Self {
+                name: docker_host.clone(),
+
+                endpoint: Endpoint::parse(&docker_host, env_tls_files())
+                    //
+                    .map_err(|source| HostError::InvalidDockerHost(Box::new(source)))?,
+            });
+        }
+
+        let context_name = match env::var("DOCKER_CONTEXT") {
+            Ok(context_name) => Some(context_name),
+
+            Err(_) => current_context()?,
+        };
+
+        if let Some(context_name) = context_name.filter(|context_name| context_name != "default") {
+            let endpoint = context(&context_name)?
+                //
+                .ok_or_else(|| HostError::NoSuchContext(context_name.clone()))?;
+
+            return Ok(Self {
+                name: context_name,
+
+                endpoint,
+            });
+        }
+
+        Ok(Self {
+            name: "default".to_string(),
+
+            endpoint: Endpoint::Unix(default_socket()),
+        })
+    }
+}
+
+impl Endpoint {
+    pub fn parse(endpoint: &str, tls: Option<TlsFiles>) -> Result<Self, HostError> {
+        if endpoint.starts_with("unix://") {
+            return Ok(Self::Unix(endpoint.to_string()));
+        }
+
+        if endpoint.starts_with("tcp://") || endpoint.starts_with("http://") {
+            return Ok(Self::Tcp {
+                address: endpoint.to_string(),
+
+                tls,
+            });
+        }
+
+        if endpoint.starts_with("https://") {
+            // like the Docker CLI, an https endpoint without explicit files uses the ones in ~/.docker
+            let tls = tls
+                //
+                .or_else(|| dirs::home_dir().map(|home| TlsFiles::in_directory(home.join(".docker"))))
+                //
+                .ok_or(HostError::NoHomeDirectory)?;
+
+            return Ok(Self::Tcp {
+                address: endpoint.to_string(),
+
+                tls: Some(tls),
+            });
+        }
+
+        if let Some(rest) = endpoint.strip_prefix("ssh://") {
+            // ssh://admin@media-1:22/run/user/1000/docker.sock, where the port and the socket path are optional
+            let (authority, socket) = match rest.split_once('/') {
+                Some((authority, path)) => (authority, format!("/{path}")),
+
+                None => (rest, "/var/run/docker.sock".to_string()),
+            };
+
+            let (destination, port) = match authority.rsplit_once(':') {
+                Some((destination, port)) => {
+                    let port = port
+                        //
+                        .parse()
+                        //
+                        .map_err(|_| HostError::InvalidPort(port.to_string()))?;
+
+                    (destination.to_string(), Some(port))
+                }
+
+                None => (authority.to_string(), None),
+            };
+
+            if destination.is_empty() {
+                return Err(HostError::MissingHost(endpoint.to_string()));
+            }
+
+            return Ok(Self::Ssh {
+                destination,
+
+                port,
+
+                socket,
+            });
+        }
+
+        Err(HostError::UnsupportedEndpoint(endpoint.to_string()))
+    }
+
+    /// Whether the daemon runs on this machine, so that host paths can be checked before they are mounted.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Unix(_))
+    }
+}
+
+/// Docker's socket, or else Podman's Docker-compatible one, rootless first.
+fn default_socket() -> String {
+    let docker = DEFAULT_SOCKET.trim_start_matches("unix://");
+
+    let podman_rootless = env::var_os("XDG_RUNTIME_DIR")
+        //
+        .map(|directory| PathBuf::from(directory).join("podman").join("podman.sock"));
+
+    let podman = PathBuf::from("/run/podman/podman.sock");
+
+    [Some(PathBuf::from(docker)), podman_rootless, Some(podman)]
+        //
+        .into_iter()
+        //
+        .flatten()
+        //
+        .find(|path| path.exists())
+        //
+        .map(|path| format!("unix://{}", path.display()))
+        //
+        .unwrap_or_else(|| DEFAULT_SOCKET.to_string())
+}
+
+/// TLS files as configured for the Docker CLI, with `DOCKER_TLS_VERIFY` and `DOCKER_CERT_PATH`.
+fn env_tls_files() -> Option<TlsFiles> {
+    env::var_os("DOCKER_TLS_VERIFY")?;
+
+    let directory = env::var_os("DOCKER_CERT_PATH")
+        //
+        .map(PathBuf::from)
+        //
+        .or_else(|| dirs::home_dir().map(|home| home.join(".docker")))?;
+
+    Some(TlsFiles::in_directory(directory))
+}
+
+fn docker_config_dir() -> Result<PathBuf, HostError> {
+    if let Some(directory) = env::var_os("DOCKER_CONFIG") {
+        return Ok(PathBuf::from(directory));
+    }
+
+    let home = dirs::home_dir()
+        //
+        .ok_or(HostError::NoHomeDirectory)?;
+
+    Ok(home.join(".docker"))
+}
+
+/// The context selected with `docker context use`.
+fn current_context() -> Result<Option<String>, HostError> {
+    #[derive(Deserialize)]
+    struct DockerConfig {
+        #[serde(rename = "currentContext")]
+        current_context: Option<String>,
+    }
+
+    let path = docker_config_dir()?.join("config.json");
+
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+
+    let DockerConfig { current_context } = serde_json::from_str(&contents)
+        //
+        .map_err(|source| HostError::Parse { path, source })?;
+
+    Ok(current_context)
+}
+
+/// The endpoint of a Docker context, from the files `docker context create` writes.
+fn context(name: &str) -> Result<Option<Endpoint>, HostError> {
+    #[derive(Deserialize)]
+    struct Meta {
+        #[serde(rename = "Endpoints")]
+        endpoints: Endpoints,
+    }
+
+    #[derive(Deserialize)]
+    struct Endpoints {
+        docker: DockerEndpoint,
+    }
+
+    #[derive(Deserialize)]
+    struct DockerEndpoint {
+        #[serde(rename = "Host")]
+        host: String,
+    }
+
+    // contexts are stored by the digest of their name
+    let digest = ring::digest::digest(&ring::digest::SHA256, name.as_bytes());
+
+    let directory_name = digest
+        //
+        .as_ref()
+        //
+        .iter()
+        //
+        .map(|byte| format!("{byte:02x}"))
+        //
+        .collect::<String>();
+
+    let contexts_dir = docker_config_dir()?.join("contexts");
+
+    let meta_path = contexts_dir.join("meta").join(&directory_name).join("meta.json");
+
+    let Ok(contents) = fs::read_to_string(&meta_path) else {
+        return Ok(None);
+    };
+
+    let Meta {
+        endpoints: Endpoints {
+            docker: DockerEndpoint { host },
+        },
+    } = serde_json::from_str(&contents)
+        //
+        .map_err(|source| HostError::Parse {
+            path: meta_path.clone(),
+
+            source,
+        })?;
+
+    let tls_dir = contexts_dir.join("tls").join(&directory_name).join("docker");
+
+    let tls = tls_dir
+        //
+        .join("cert.pem")
+        //
+        .exists()
+        //
+        .then(|| TlsFiles::in_directory(tls_dir));
+
+    Endpoint::parse(&host, tls)
+        //
+        .map_err(|source| HostError::InvalidContext {
+            name: name.to_string(),
+
+            source: Box::new(source),
+        })
+        //
+        .map(Some)
+}
+
+/// An SSH process that forwards a local socket to the daemon's socket on another machine, for as long as it lives.
+pub struct Tunnel {
+    child: Child,
+
+    /// Path of the local socket.
+    pub socket: PathBuf,
+}
+
+impl Tunnel {
+    pub async fn open(destination: &str, port: Option<u16>, remote_socket: &str) -> Result<Self, HostError> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let socket = env::temp_dir().join(format!(
+            "mayo-ssh-{}-{}.sock",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut command = Command::new("ssh");
+
+        command
+            //
+            .args(["-nNT", "-o", "ExitOnForwardFailure=yes", "-o", "BatchMode=yes"])
+            //
+            .arg("-L")
+            //
+            .arg(format!("{}:{remote_socket}", socket.display()))
+            //
+            .stdin(Stdio::null())
+            //
+            .stdout(Stdio::null())
+            //
+            .stderr(Stdio::piped());
+
+        if let Some(port) = port {
+            command.arg("-p").arg(port.to_string());
+        }
+
+        command.arg(destination);
+
+        let child = command
+            //
+            .spawn()
+            //
+            .map_err(HostError::Ssh)?;
+
+        let mut tunnel = Self { child, socket };
+
+        let started_at = Instant::now();
+
+        while !tunnel.socket.exists() {
+            if let Some(status) = tunnel.child.try_wait().map_err(HostError::Ssh)? {
+                let mut stderr = String::new();
+
+                if let Some(mut pipe) = tunnel.child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+
+                return Err(HostError::SshExited {
+                    destination: destination.to_string(),
+
+                    status,
+
+                    stderr: stderr.trim().to_string(),
+                });
+            }
+
+            if started_at.elapsed() >= TUNNEL_TIMEOUT {
+                return Err(HostError::TunnelTimeout {
+                    destination: destination.to_string(),
+
+                    seconds: TUNNEL_TIMEOUT.as_secs(),
+                });
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(tunnel)
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+
+        let _ = self.child.wait();
+
+        let _ = fs::remove_file(&self.socket);
+    }
+}
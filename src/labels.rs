@@ -1,3 +1,96 @@
+//! Labels that mark a container as a server, and the versioned encoding of its parameters.
+//!
+//! The parameters label holds `{"schema": N, "parameters": {...}}`. Schema 1 predates the envelope and is the plain
+//! JSON of [`Parameters`]. Whenever the encoding of the parameters changes, [`CURRENT_SCHEMA`] is bumped and a
+//! migration from the previous schema is appended to [`MIGRATIONS`], so that older labels keep being readable.
+
+use crate::error::ParametersError;
+use crate::parameters::Parameters;
+
+use serde_json::Value;
+use serde_json::json;
+
 pub const LABEL_KEY_CLI_VERSION: &str = "mayo.cli_version";
 
 pub const LABEL_KEY_PARAMETERS: &str = "mayo.parameters";
+
+/// Name of the server that a server was cloned from.
+pub const LABEL_KEY_ORIGIN: &str = "mayo.origin";
+
+pub const CURRENT_SCHEMA: u32 = 2;
+
+/// Upgrades of the encoded parameters from each schema to the next, starting with schema 1.
+const MIGRATIONS: [fn(Value) -> Value; CURRENT_SCHEMA as usize - 1] = [
+    //
+    migrate_from_1,
+];
+
+/// Parameters read from a label, with the schema they were encoded in.
+#[derive(Clone, Debug)]
+pub struct DecodedParameters {
+    pub schema: u32,
+
+    pub parameters: Parameters,
+}
+
+pub fn encode_parameters(parameters: &Parameters) -> String {
+    json!({
+        "schema": CURRENT_SCHEMA,
+        "parameters": parameters,
+    })
+    .to_string()
+}
+
+/// Decodes the parameters label, upgrading older schemas in memory.
+///
+/// Parameters of a newer schema are decoded as far as this CLI understands them, and fail only when they cannot be.
+pub fn decode_parameters(label: &str) -> Result<DecodedParameters, ParametersError> {
+    let value = serde_json::from_str::<Value>(label)?;
+
+    let (schema, mut parameters) = match value {
+        Value::Object(mut object) if object.contains_key("schema") => {
+            let schema = object
+                //
+                .get("schema")
+                //
+                .and_then(Value::as_u64)
+                //
+                .and_then(|schema| u32::try_from(schema).ok())
+                //
+                .filter(|schema| *schema >= 2)
+                //
+                .ok_or(ParametersError::InvalidSchema)?;
+
+            let parameters = object
+                //
+                .remove("parameters")
+                //
+                .ok_or(ParametersError::InvalidSchema)?;
+
+            (schema, parameters)
+        }
+
+        value => (1, value),
+    };
+
+    for migration in MIGRATIONS.iter().skip(schema as usize - 1) {
+        parameters = migration(parameters);
+    }
+
+    let parameters = serde_json::from_value::<Parameters>(parameters)
+        //
+        .map_err(|source| {
+            if schema > CURRENT_SCHEMA {
+                ParametersError::NewerSchema(schema)
+            } else {
+                ParametersError::Malformed(source)
+            }
+        })?;
+
+    Ok(DecodedParameters { schema, parameters })
+}
+
+/// Schema 2 introduced the envelope, the parameters themselves are unchanged.
+fn migrate_from_1(parameters: Value) -> Value {
+    parameters
+}
@@ -1,8 +1,18 @@
+pub mod acme;
+pub mod adopt;
+pub mod backup;
+pub mod certificate;
 pub mod cli;
-pub mod command;
+pub mod compose;
+pub mod config;
+pub mod engine;
+pub mod error;
+pub mod fleet;
+pub mod host;
 pub mod labels;
 pub mod mayo;
 pub mod parameters;
 pub mod registry;
+pub mod schedule;
 pub mod server;
 pub mod versioning;
@@ -1,23 +1,47 @@
+use crate::adopt;
+use crate::adopt::Adoption;
+use crate::compose;
+use crate::compose::Export;
+use crate::engine::DockerEngine;
+use crate::engine::Engine;
+use crate::engine::EngineInfo;
+use crate::engine::EngineKind;
+use crate::error::AdoptError;
+use crate::error::BackupError;
+use crate::error::EngineError;
+use crate::error::Error;
+use crate::error::ParametersError;
+use crate::error::Result;
+use crate::error::ServerError;
+use crate::error::Warning;
+use crate::host::Host;
 use crate::labels::*;
+use crate::parameters::Name;
 use crate::parameters::Parameters;
+use crate::parameters::Port;
+use crate::parameters::Storage;
 use crate::registry;
+use crate::server::HiddenContainer;
+use crate::server::HiddenReason;
 use crate::server::Server;
 use crate::versioning;
 
 use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
 
-use anyhow::*;
-
-use bollard::Docker;
 use bollard::container;
-use bollard::container::CreateContainerOptions;
-use bollard::container::ListContainersOptions;
-use bollard::container::StartContainerOptions;
-use bollard::image::CreateImageOptions;
-use bollard::image::ListImagesOptions;
 use bollard::models::*;
 
+use bytes::Bytes;
+
 use futures_util::StreamExt;
+use futures_util::stream;
 
 use indicatif::MultiProgress;
 use indicatif::ProgressBar;
@@ -25,45 +49,171 @@ use indicatif::ProgressStyle;
 
 use semver::Version;
 
-pub struct Mayo {
-    docker: Docker,
-}
+use tokio::sync::mpsc;
 
-impl From<Docker> for Mayo {
-    fn from(docker: Docker) -> Self {
-        Self { docker }
-    }
+pub const MAYO_DATA_PATH: &str = "/mayo/.data";
+
+/// The user and group that servers with the hardened security profile run as.
+pub const HARDENED_USER: &str = "1000:1000";
+
+pub const HARDENED_UID: u32 = 1000;
+
+/// How many chunks of an image being copied between engines may wait for the importing engine.
+const IMAGE_CHUNKS_IN_FLIGHT: usize = 16;
+
+pub struct Mayo<E = DockerEngine> {
+    engine: E,
+
+    /// Name of the host, for display.
+    host: String,
+
+    /// Whether the daemon runs on this machine, so that host paths can be checked before they are mounted.
+    is_local: bool,
+
+    /// Receives what goes wrong without failing an operation, which is dropped unless a caller asks for it.
+    on_warning: Box<dyn Fn(Warning) + Send + Sync>,
 }
 
 impl Mayo {
-    pub fn try_new() -> Result<Self> {
-        Docker::connect_with_defaults()
+    /// Connects to the daemon chosen with `--host`, or to the Docker CLI's default one.
+    pub async fn try_new(host: Option<&str>) -> Result<Self> {
+        let host = Host::resolve(host)?;
+
+        Self::connect(&host).await
+    }
+
+    pub async fn connect(host: &Host) -> Result<Self> {
+        let engine = DockerEngine::connect(host)
             //
-            .map(Into::into)
+            .await
             //
-            .context("failed to connect to the Docker daemon")
+            .map_err(Error::engine("failed to connect to the engine"))?;
+
+        Ok(Self::with_engine(engine, &host.name, host.endpoint.is_local()))
     }
+}
 
-    async fn pull_image(&self, reference: &str) -> Result<()> {
-        let options = CreateImageOptions {
+impl<E: Engine> Mayo<E> {
+    /// Manages the servers of any engine, e.g. a [`MemoryEngine`](crate::engine::MemoryEngine) in tests.
+    pub fn with_engine(engine: E, host: &str, is_local: bool) -> Self {
+        Self {
+            engine,
+
+            host: host.to_string(),
+
+            is_local,
+
+            on_warning: Box::new(|_| {}),
+        }
+    }
+
+    /// Hands the warnings of later operations to `on_warning`, e.g. to print them.
+    pub fn on_warning(mut self, on_warning: impl Fn(Warning) + Send + Sync + 'static) -> Self {
+        self.on_warning = Box::new(on_warning);
+
+        self
+    }
+
+    pub(crate) fn warn(&self, warning: Warning) {
+        (self.on_warning)(warning);
+    }
+
+    /// Finds out which engine serves the API, and how it runs.
+    pub async fn engine_info(&self) -> Result<EngineInfo> {
+        let version = self
             //
-            from_image: reference,
+            .engine
             //
-            ..Default::default()
+            .version()
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to get the version of the engine"))?;
+
+        let info = self
+            //
+            .engine
+            //
+            .info()
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to get information about the engine"))?;
+
+        // Podman's compatibility API describes itself as a component named "Podman Engine"
+        let kind = if version
+            //
+            .components
+            //
+            .iter()
+            //
+            .flatten()
+            //
+            .any(|component| component.name.starts_with("Podman"))
+        {
+            EngineKind::Podman
+        } else {
+            EngineKind::Docker
         };
 
+        let rootless = info
+            //
+            .security_options
+            //
+            .iter()
+            //
+            .flatten()
+            //
+            .any(|option| option == "name=rootless");
+
+        Ok(EngineInfo {
+            kind,
+
+            version: version.version.unwrap_or_else(|| "unknown".to_string()),
+
+            api_version: version.api_version.unwrap_or_else(|| "unknown".to_string()),
+
+            platform: format!(
+                "{}/{}",
+                version.os.as_deref().unwrap_or("unknown"),
+                version.arch.as_deref().unwrap_or("unknown")
+            ),
+
+            rootless,
+        })
+    }
+
+    /// The host's `net.ipv4.ip_unprivileged_port_start`, when the daemon runs on this machine.
+    pub fn unprivileged_port_start(&self) -> Option<u16> {
+        if !self.is_local {
+            return None;
+        }
+
+        fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+            //
+            .ok()?
+            //
+            .trim()
+            //
+            .parse()
+            //
+            .ok()
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.is_local
+    }
+
+    async fn pull_image(&self, reference: &str) -> Result<()> {
         let mut stream = self
             //
-            .docker
+            .engine
             //
-            .create_image(
-                //
-                Some(options),
-                //
-                None,
-                //
-                None,
-            );
+            .pull_image(reference);
 
         let multi_progress = MultiProgress::new();
 
@@ -81,7 +231,7 @@ impl Mayo {
                 ..
             } = message_result
                 //
-                .context("failed to read a message")?;
+                .map_err(Error::engine("failed to pull the image"))?;
 
             if let Some(layer_id) = id {
                 let progress_bar = layers
@@ -98,35 +248,28 @@ impl Mayo {
                         progress_bar
                     });
 
-                if let Some(progress_detail) = progress_detail {
-                    if let (
-                        //
-                        Some(current),
-                        //
-                        Some(total),
-                    ) = (
-                        //
-                        progress_detail.current,
-                        //
-                        progress_detail.total,
-                    ) {
-                        if total > 0 {
-                            let total: u64 = total.try_into().unwrap();
+                if let Some(ProgressDetail {
+                    //
+                    current: Some(current),
+                    //
+                    total: Some(total),
+                }) = progress_detail
+                    && total > 0
+                {
+                    let total: u64 = total.try_into().unwrap();
 
-                            if progress_bar.length().unwrap() != total {
-                                let template = "{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({percent}%)";
+                    if progress_bar.length().unwrap() != total {
+                        let template = "{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({percent}%)";
 
-                                let style = ProgressStyle::with_template(template).unwrap().progress_chars("##-");
+                        let style = ProgressStyle::with_template(template).unwrap().progress_chars("##-");
 
-                                progress_bar.set_style(style);
+                        progress_bar.set_style(style);
 
-                                progress_bar.set_length(total);
-                            }
+                        progress_bar.set_length(total);
+                    }
 
-                            if current >= 0 {
-                                progress_bar.set_position(current.try_into().unwrap());
-                            }
-                        }
+                    if current >= 0 {
+                        progress_bar.set_position(current.try_into().unwrap());
                     }
                 }
 
@@ -148,27 +291,15 @@ impl Mayo {
     }
 
     async fn find_existing_image_by_reference(&self, reference: &str) -> Result<Option<String>> {
-        let mut filters = HashMap::default();
-
-        filters.insert("reference", vec![reference]);
-
-        let options = ListImagesOptions {
-            all: false,
-
-            filters,
-
-            digests: false,
-        };
-
         let mut response = self
             //
-            .docker
+            .engine
             //
-            .list_images(Some(options))
+            .list_images(reference)
             //
             .await
             //
-            .context("failed to list images")?;
+            .map_err(Error::engine("failed to list images"))?;
 
         let image_id = response
             //
@@ -180,319 +311,1671 @@ impl Mayo {
     }
 
     async fn version_to_image(&self, version: &Version) -> Result<String> {
-        // e.g. ghcr.io/mayo-dayo/app:0.2.0
-        let reference = format!(
-            //
-            "{}/{}/{}:{}",
-            //
-            registry::REGISTRY,
-            //
-            registry::USERNAME,
-            //
-            registry::REPOSITORY,
-            //
-            version
-        );
+        let reference = image_reference(version);
 
         loop {
             let image_id = self
                 //
                 .find_existing_image_by_reference(&reference)
                 //
-                .await
-                //
-                .context("failed to look for an existing image")?;
+                .await?;
 
             if let Some(image_id) = image_id {
                 return Ok(image_id);
             } else {
                 self.pull_image(&reference)
                     //
-                    .await
-                    //
-                    .context("failed to pull the image")?;
+                    .await?;
             }
         }
     }
 
-    pub async fn create_server(&self, parameters: Parameters) -> Result<String> {
-        let image_id = self
-            //
-            .version_to_image(&parameters.version)
+    /// Makes the image of an app version available, pulling it from the registry or else copying it from `source`.
+    ///
+    /// Copying lets servers move to daemons that cannot reach the registry.
+    pub async fn transfer_image<S: Engine>(&self, version: &Version, source: &Mayo<S>) -> Result<()> {
+        let reference = image_reference(version);
+
+        if self.find_existing_image_by_reference(&reference).await?.is_some() {
+            return Ok(());
+        }
+
+        let Err(pull_error) = self.pull_image(&reference).await else {
+            return Ok(());
+        };
+
+        self.warn(Warning::PullFailed {
+            reference: reference.clone(),
+
+            error: Box::new(pull_error),
+        });
+
+        source
             //
-            .await
+            .version_to_image(version)
             //
-            .context("failed to get the image")?;
+            .await?;
 
-        let mut labels = HashMap::<String, String>::default();
+        // the engine API takes the archive as a `'static` stream, so the export reaches it through a channel,
+        // chunk by chunk, rather than all at once in memory
+        let (sender, receiver) = mpsc::channel(IMAGE_CHUNKS_IN_FLIGHT);
 
-        labels.insert(
+        let export = async {
+            let sender = sender;
+
+            let mut export = source
+                //
+                .engine
+                //
+                .export_image(&reference);
+
+            while let Some(bytes) = export.next().await {
+                // the import has stopped reading, and reports why
+                if sender.send(bytes?).await.is_err() {
+                    break;
+                }
+            }
+
+            Ok(())
+        };
+
+        let archive = stream::unfold(receiver, |mut receiver| async move {
+            receiver
+                //
+                .recv()
+                //
+                .await
+                //
+                .map(|bytes| (bytes, receiver))
+        });
+
+        let (export_result, import_result) = tokio::join!(export, self.engine.import_image(archive.boxed()));
+
+        // an export error ends the archive early, which the import then reports as truncated
+        export_result.map_err(Error::engine("failed to export the image"))?;
+
+        import_result.map_err(Error::engine("failed to copy the image"))?;
+
+        if self.find_existing_image_by_reference(&reference).await?.is_none() {
+            return Err(ServerError::ImageMissing(reference).into());
+        }
+
+        Ok(())
+    }
+
+    /// Creates and starts a server, recording the server it was cloned from, if any.
+    pub async fn create_server(&self, parameters: Parameters, origin: Option<&str>) -> Result<String> {
+        let container_name = format!("mayo-{}", parameters.name);
+
+        let id = self
             //
-            LABEL_KEY_CLI_VERSION.to_string(),
+            .create_server_container(parameters, origin, &container_name)
             //
-            versioning::current_cli_version().to_string(),
-        );
+            .await?;
 
-        labels.insert(
+        self
             //
-            LABEL_KEY_PARAMETERS.to_string(),
+            .engine
             //
-            parameters.encode_for_label(),
-        );
-
-        let Parameters {
-            name,
+            .start_container(&id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to start the container"))?;
 
-            version: _,
+        Ok(id)
+    }
 
-            port,
+    /// Recreates a server's container with new parameters, keeping its data.
+    ///
+    /// The new container is created before the old one is stopped, so the downtime is limited to the time it takes
+    /// the new one to become healthy. If it does not, the old container is started again.
+    pub async fn replace_server(&self, server: &Server, parameters: Parameters, timeout: Duration) -> Result<String> {
+        server.check_writable()?;
 
-            authentication,
+        if server.parameters.name != parameters.name {
+            return Err(ServerError::Renamed.into());
+        }
 
-            tls,
-        } = parameters;
+        let container_name = format!("mayo-{}", parameters.name);
 
-        const MAYO_DATA_PATH: &str = "/mayo/.data";
+        let next_name = format!("{container_name}-next");
 
-        let mut env = vec![
+        let next_id = self
             //
-            format!("BUN_PORT={port}"),
+            .create_server_container(parameters, server.origin.as_deref(), &next_name)
             //
-            format!("MAYO_DATA_PATH={MAYO_DATA_PATH}"),
-        ];
+            .await?;
+
+        let result = async {
+            self.stop_container(&server.id)
+                //
+                .await?;
 
-        if let Some((crt, key)) = tls.into_inner() {
-            env.extend_from_slice(&[
+            self
                 //
-                format!("MAYO_TLS_CRT={crt}"),
+                .engine
                 //
-                format!("MAYO_TLS_KEY={key}"),
-            ])
-        }
+                .start_container(&next_id)
+                //
+                .await
+                //
+                .map_err(Error::engine("failed to start the new container"))?;
 
-        if authentication.is_required() {
-            env.push("MAYO_AUTHENTICATION=required".to_string());
+            self.wait_until_ready(&next_id, timeout)
+                //
+                .await
         }
+        .await;
 
-        let mounts = vec![Mount {
-            target: Some(MAYO_DATA_PATH.to_string()),
-
-            source: Some(format!("mayo-{name}")),
+        if let Err(error) = result {
+            // the server comes first, the new container can still be removed by hand
+            let restart = self
+                //
+                .engine
+                //
+                .start_container(&server.id)
+                //
+                .await
+                //
+                .map_err(Error::engine("failed to restart the previous container"));
 
-            typ: Some(MountTypeEnum::VOLUME),
+            if let Err(removal_error) = self.remove_container(&next_id).await {
+                self.warn(Warning::ContainerLeft {
+                    id: next_name,
 
-            read_only: Some(false),
+                    error: Box::new(removal_error),
+                });
+            }
 
-            ..Default::default()
-        }];
+            return Err(match restart {
+                Ok(()) => ServerError::Restored(Box::new(error)),
 
-        let host_config = HostConfig {
-            mounts: Some(mounts),
+                Err(restart) => ServerError::NotRestored {
+                    id: server.id.clone(),
 
-            network_mode: Some("host".to_string()),
+                    restart: Box::new(restart),
 
-            restart_policy: Some(RestartPolicy {
-                name: Some(RestartPolicyNameEnum::UNLESS_STOPPED),
+                    source: Box::new(error),
+                },
+            }
+            .into());
+        }
 
-                maximum_retry_count: None,
-            }),
+        self.remove_container(&server.id)
+            //
+            .await?;
 
-            ..Default::default()
-        };
+        // the old name is only just free, so a failed rename is tried once more before giving up
+        let mut renamed = self.rename_container(&next_id, &container_name).await;
 
-        let config = container::Config {
-            image: Some(image_id),
+        if renamed.is_err() {
+            renamed = self.rename_container(&next_id, &container_name).await;
+        }
 
-            env: Some(env),
+        renamed.map_err(|source| ServerError::NotRenamed {
+            from: next_name,
 
-            labels: Some(labels),
+            to: container_name,
 
-            host_config: Some(host_config),
+            source: Box::new(source),
+        })?;
 
-            ..Default::default()
-        };
+        Ok(next_id)
+    }
 
-        let options = CreateContainerOptions {
-            name: format!("mayo-{name}"),
+    pub async fn start_server(&self, server: &Server) -> Result<()> {
+        self
+            //
+            .engine
+            //
+            .start_container(&server.id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to start the container"))
+    }
 
-            ..Default::default()
-        };
+    pub async fn stop_server(&self, server: &Server) -> Result<()> {
+        self.stop_container(&server.id).await
+    }
 
-        let ContainerCreateResponse {
-            //
-            id,
-            ..
-        } = self
+    async fn stop_container(&self, id: &str) -> Result<()> {
+        self
             //
-            .docker
+            .engine
             //
-            .create_container(Some(options), config)
+            .stop_container(id, 10)
             //
             .await
             //
-            .context("failed to create a container")?;
+            .map_err(Error::engine("failed to stop the container"))
+    }
 
+    async fn rename_container(&self, id: &str, name: &str) -> Result<()> {
         self
             //
-            .docker
+            .engine
             //
-            .start_container(&id, None::<StartContainerOptions<String>>)
+            .rename_container(id, name)
             //
             .await
             //
-            .context("failed to start the container")?;
-
-        Ok(id)
+            .map_err(Error::engine("failed to rename the container"))
     }
 
-    pub async fn list_servers(&self) -> Result<Vec<Server>> {
-        let mut summaries = {
-            let mut filters = HashMap::default();
-
-            // filter out non-mayo containers
-            filters.insert("label", vec![LABEL_KEY_CLI_VERSION]);
-
-            let options = ListContainersOptions {
-                all: true,
+    async fn remove_container(&self, id: &str) -> Result<()> {
+        self
+            //
+            .engine
+            //
+            .remove_container(id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to remove the container"))
+    }
 
-                limit: None,
+    async fn create_server_container(
+        &self,
+        parameters: Parameters,
+        origin: Option<&str>,
+        container_name: &str,
+    ) -> Result<String> {
+        let missing_secrets = parameters.missing_secrets();
 
-                size: false,
+        if !missing_secrets.is_empty() {
+            return Err(ServerError::MissingSecrets(missing_secrets).into());
+        }
 
-                filters,
-            };
+        let engine = self.engine_info().await?;
 
-            let response = self
-                //
-                .docker
+        // the paths of a remote daemon are not on this machine, so it is up to the daemon to complain about them
+        if self.is_local {
+            parameters
                 //
-                .list_containers(Some(options))
+                .storage
                 //
-                .await
+                .check(parameters.security, engine.rootless)
                 //
-                .context("failed to list containers")?;
-
-            response
-        };
+                .map_err(ServerError::UnusableDataDirectory)?;
 
-        // filter out containers with incompatible cli version
-        {
-            summaries.retain(|summary| {
-                let ContainerSummary {
-                    //
-                    labels,
-                    ..
-                } = summary;
+            parameters
+                //
+                .libraries
+                //
+                .check(parameters.security, engine.rootless)
+                //
+                .map_err(ServerError::UnusableLibrary)?;
+        }
 
-                let is_cli_compatible = labels
-                    //
-                    .as_ref()
-                    //
-                    .map(|labels| {
-                        labels
-                            //
-                            .get(LABEL_KEY_CLI_VERSION)
-                            //
-                            .map(|value| {
-                                //
-                                Version::parse(value)
-                                    //
-                                    .ok()
-                            })
-                            //
-                            .flatten()
-                            //
-                            .map(|version| versioning::is_compatible_cli_version(&version))
-                    })
-                    //
-                    .flatten()
-                    //
-                    .unwrap_or(false);
+        if !engine.can_listen_on(parameters.port.get(), self.unprivileged_port_start()) {
+            return Err(ServerError::PrivilegedPort {
+                kind: engine.kind,
 
-                is_cli_compatible
-            });
+                port: parameters.port.get(),
+            }
+            .into());
         }
 
-        // filter out containers with incompatible app version
-        {
-            let mut is_image_compatible = HashMap::<String, bool>::default();
+        let image_id = self
+            //
+            .version_to_image(&parameters.version)
+            //
+            .await?;
 
-            let mut index = 0;
+        if let Storage::Volume = parameters.storage {
+            self.ensure_volume(&parameters.storage.source(&parameters.name)).await?;
+        }
 
-            while let Some(summary) = summaries.get(index) {
-                let ContainerSummary {
-                    //
-                    image_id,
-                    ..
-                } = summary;
+        let prepares_data_volume = prepares_data_volume(&parameters);
 
-                if let Some(image_id) = image_id {
-                    if let Some(true) = is_image_compatible.get(image_id) {
-                        index += 1;
+        let config = server_container_config(parameters, origin, image_id.clone(), engine.restart_policy());
 
-                        continue;
-                    }
+        if prepares_data_volume {
+            self.prepare_data_volume(&image_id, &config)
+                //
+                .await
+                //
+                .map_err(|error| ServerError::DataVolume(Box::new(error)))?;
+        }
 
-                    let ImageInspect {
-                        //
-                        repo_tags,
-                        ..
-                    } = self
-                        //
-                        .docker
-                        //
-                        .inspect_image(&image_id)
-                        //
-                        .await
-                        //
-                        .context("failed to inspect an image")?;
-
-                    if let Some(repo_tags) = repo_tags {
-                        let tags = repo_tags
-                            //
-                            .iter()
-                            //
-                            .flat_map(|repo_tag| {
-                                repo_tag
-                                    //
-                                    .split_once(':')
-                                    //
-                                    .map(|(_, tag)| tag)
-                            });
-
-                        let has_compatible_version = versioning::tags_to_compatible_app_versions(tags)
-                            //
-                            .next()
-                            //
-                            .is_some();
-
-                        is_image_compatible.insert(
-                            //
-                            image_id.clone(),
-                            //
-                            has_compatible_version,
-                        );
-
-                        if has_compatible_version {
-                            index += 1;
-
-                            continue;
-                        }
-                    }
-                }
+        self
+            //
+            .engine
+            //
+            .create_container(Some(container_name), config)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to create a container"))
+    }
 
-                summaries.swap_remove(index);
-            }
-        }
+    /// Hands the data mount over to the unprivileged user of the hardened profile.
+    ///
+    /// Docker creates named volumes owned by root, so a short-lived root container with the same mounts runs `chown`
+    /// before the server itself is created.
+    async fn prepare_data_volume(&self, image_id: &str, server_config: &container::Config<String>) -> Result<()> {
+        let config = data_volume_preparation_config(image_id.to_string(), server_config);
 
-        let servers = summaries
+        let id = self
             //
-            .into_iter()
+            .engine
             //
-            .filter_map(|summary| Server::try_from(summary).ok())
+            .create_container(None, config)
             //
-            .collect::<Vec<_>>();
+            .await
+            //
+            .map_err(Error::engine("failed to create a container"))?;
+
+        let result = self.run_to_completion(&id).await;
+
+        self.remove_container_after(&id, result)
+            //
+            .await
+    }
+
+    /// Removes a container that has served its purpose, returning the error of `result` rather than the one of the
+    /// removal when both failed, since it is what went wrong in the first place.
+    async fn remove_container_after(&self, id: &str, result: Result<()>) -> Result<()> {
+        let removal = self.remove_container(id).await;
+
+        match (result, removal) {
+            (Err(error), Err(removal_error)) => {
+                self.warn(Warning::ContainerLeft {
+                    id: id.to_string(),
+
+                    error: Box::new(removal_error),
+                });
+
+                Err(error)
+            }
+
+            (Err(error), Ok(())) => Err(error),
+
+            (Ok(()), removal) => removal,
+        }
+    }
+
+    async fn run_to_completion(&self, id: &str) -> Result<()> {
+        self
+            //
+            .engine
+            //
+            .start_container(id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to start the container"))?;
+
+        let status_code = self
+            //
+            .engine
+            //
+            .wait_container(id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to wait for the container"))?;
+
+        if status_code != 0 {
+            return Err(ServerError::HelperExited(status_code).into());
+        }
+
+        Ok(())
+    }
+
+    pub async fn wait_until_healthy(&self, id: &str, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let ContainerInspectResponse {
+                //
+                state,
+                ..
+            } = self
+                //
+                .engine
+                //
+                .inspect_container(id)
+                //
+                .await
+                //
+                .map_err(Error::engine("failed to inspect the container"))?;
+
+            let ContainerState {
+                //
+                status,
+                //
+                health,
+                //
+                exit_code,
+                ..
+            } = state.unwrap_or_default();
+
+            if let Some(ContainerStateStatusEnum::EXITED | ContainerStateStatusEnum::DEAD) = status {
+                return Err(ServerError::Exited(exit_code.unwrap_or_default()).into());
+            }
+
+            match health.and_then(|health| health.status) {
+                Some(HealthStatusEnum::HEALTHY) => {
+                    return Ok(());
+                }
+
+                Some(HealthStatusEnum::UNHEALTHY) => {
+                    return Err(ServerError::Unhealthy.into());
+                }
+
+                // containers created without a health check are considered ready once running
+                None | Some(HealthStatusEnum::NONE | HealthStatusEnum::EMPTY) => {
+                    if let Some(ContainerStateStatusEnum::RUNNING) = status {
+                        return Ok(());
+                    }
+                }
+
+                Some(HealthStatusEnum::STARTING) => {
+                    //
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(ServerError::Timeout(timeout.as_secs()).into());
+            }
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Waits until the server is healthy, attaching its last log lines to the error if it is not.
+    pub async fn wait_until_ready(&self, id: &str, timeout: Duration) -> Result<()> {
+        let Err(error) = self
+            //
+            .wait_until_healthy(id, timeout)
+            //
+            .await
+        else {
+            return Ok(());
+        };
+
+        let lines = self
+            //
+            .tail_logs(id, 20)
+            //
+            .await
+            //
+            .unwrap_or_default();
+
+        if lines.is_empty() {
+            Err(error)
+        } else {
+            Err(ServerError::NotReady {
+                logs: lines,
+
+                source: Box::new(error),
+            }
+            .into())
+        }
+    }
+
+    pub async fn tail_logs(&self, id: &str, lines: usize) -> Result<Vec<String>> {
+        let mut stream = self
+            //
+            .engine
+            //
+            .logs(id, lines);
+
+        let mut result = Vec::new();
+
+        while let Some(output_result) = stream.next().await {
+            let output = output_result
+                //
+                .map_err(Error::engine("failed to read the logs"))?;
+
+            result.extend(
+                output
+                    //
+                    .to_string()
+                    //
+                    .lines()
+                    //
+                    .map(ToString::to_string),
+            );
+        }
+
+        Ok(result)
+    }
+
+    /// Removes a server's container and, if asked to, the named volume that holds its data.
+    ///
+    /// Bind-mounted data belongs to the host and is never removed.
+    pub async fn remove_server(&self, server: &Server, remove_data: bool) -> Result<()> {
+        self.remove_container(&server.id)
+            //
+            .await?;
+
+        if remove_data && let Storage::Volume = server.parameters.storage {
+            self.remove_volume(&server.parameters.storage.source(&server.parameters.name))
+                //
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn volume_exists(&self, name: &str) -> Result<bool> {
+        let volume = self
+            //
+            .engine
+            //
+            .inspect_volume(name)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to inspect the volume"))?;
+
+        Ok(volume.is_some())
+    }
+
+    /// Creates a named volume unless it exists.
+    ///
+    /// Volumes are created up front rather than by the first container that mounts them, so that they are created
+    /// the same way on Docker and Podman.
+    async fn ensure_volume(&self, name: &str) -> Result<()> {
+        if self.volume_exists(name).await? {
+            return Ok(());
+        }
+
+        self
+            //
+            .engine
+            //
+            .create_volume(name)
+            //
+            .await
+            //
+            .map_err(Error::engine(format!("failed to create the volume {name}")))
+    }
+
+    pub async fn remove_volume(&self, name: &str) -> Result<()> {
+        self
+            //
+            .engine
+            //
+            .remove_volume(name)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to remove the volume"))
+    }
+
+    /// Unpacks a tar archive of a data path, as produced by [`Self::download_data`], into a volume, which is created if
+    /// needed.
+    ///
+    /// The data goes through a short-lived container of the app's image in `version`, since a server's own container
+    /// may have a read-only root filesystem, which Docker refuses to copy into.
+    pub async fn upload_data(&self, version: &Version, volume: &str, data: File) -> Result<()> {
+        let image_id = self
+            //
+            .version_to_image(version)
+            //
+            .await?;
+
+        self.ensure_volume(volume).await?;
+
+        let mounts = vec![Mount {
+            target: Some(MAYO_DATA_PATH.to_string()),
+
+            source: Some(volume.to_string()),
+
+            typ: Some(MountTypeEnum::VOLUME),
+
+            read_only: Some(false),
+
+            ..Default::default()
+        }];
+
+        let config = container::Config {
+            image: Some(image_id),
+
+            user: Some("0:0".to_string()),
+
+            host_config: Some(HostConfig {
+                mounts: Some(mounts),
+
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        };
+
+        let id = self
+            //
+            .engine
+            //
+            .create_container(None, config)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to create a container"))?;
+
+        // a read error is passed on, so that a truncated archive fails the upload rather than restoring part of it
+        let chunks = stream::try_unfold(data, |mut data| async move {
+            let mut buffer = vec![0; 1 << 20];
+
+            let length = data.read(&mut buffer)?;
+
+            if length == 0 {
+                return Ok(None);
+            }
+
+            buffer.truncate(length);
+
+            Ok(Some((Bytes::from(buffer), data)))
+        });
+
+        // the archive's top-level directory is the data directory itself
+        let parent = Path::new(MAYO_DATA_PATH)
+            //
+            .parent()
+            //
+            .unwrap()
+            //
+            .to_string_lossy()
+            //
+            .to_string();
+
+        let result = self
+            //
+            .engine
+            //
+            .upload_to_container(&id, &parent, chunks.boxed())
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to upload the data"));
+
+        self.remove_container_after(&id, result)
+            //
+            .await
+    }
+
+    /// Streams the data path of a container as a tar archive, even while it is stopped.
+    pub async fn download_data(&self, id: &str, writer: &mut impl Write) -> Result<()> {
+        let mut stream = self
+            //
+            .engine
+            //
+            .download_from_container(id, MAYO_DATA_PATH);
+
+        while let Some(bytes_result) = stream.next().await {
+            let bytes = bytes_result
+                //
+                .map_err(Error::engine("failed to download the data"))?;
+
+            writer
+                //
+                .write_all(&bytes)
+                //
+                .map_err(BackupError::io("failed to write the data"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders a server as a compose file, with the container configuration [`create_server`](Self::create_server)
+    /// would build on this engine.
+    pub async fn export_compose(&self, server: &Server) -> Result<Export> {
+        // the label would lose what this CLI cannot read
+        server.check_writable()?;
+
+        let engine = self.engine_info().await?;
+
+        let parameters = &server.parameters;
+
+        let image = image_reference(&parameters.version);
+
+        let config = server_container_config(
+            parameters.clone(),
+            server.origin.as_deref(),
+            image.clone(),
+            engine.restart_policy(),
+        );
+
+        let preparation = prepares_data_volume(parameters)
+            //
+            .then(|| data_volume_preparation_config(image, &config));
+
+        Ok(compose::render(parameters, config, preparation)?)
+    }
+
+    /// Inspects a container that the CLI did not create, to take it over as a server named `name`.
+    pub async fn plan_adoption(&self, container: &str, name: Option<Name>) -> Result<Adoption> {
+        let inspect = self
+            //
+            .engine
+            //
+            .inspect_container(container)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to inspect the container"))?;
+
+        let image_id = inspect
+            //
+            .image
+            //
+            .clone()
+            //
+            .ok_or(AdoptError::MissingField("image"))?;
+
+        let ImageInspect {
+            //
+            repo_tags,
+            //
+            config,
+            ..
+        } = self
+            //
+            .engine
+            //
+            .inspect_image(&image_id)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to inspect the image"))?;
+
+        let image_env = config
+            //
+            .and_then(|config| config.env)
+            //
+            .unwrap_or_default();
+
+        let adoption = adopt::map_container(inspect, &repo_tags.unwrap_or_default(), &image_env, name)?;
+
+        Ok(adoption)
+    }
+
+    /// Recreates an inspected container as a server on the same data.
+    ///
+    /// The container is stopped, and renamed out of the way if it holds the server's container name, until the server
+    /// is healthy. It is removed then, with its volumes kept, or brought back if the server does not become healthy.
+    pub async fn adopt(&self, adoption: &Adoption, timeout: Duration) -> Result<String> {
+        let Adoption {
+            //
+            container_id,
+            //
+            container_name,
+            //
+            is_running,
+            //
+            parameters,
+            //
+            copy_from,
+            ..
+        } = adoption;
+
+        if self.find_server(parameters.name.as_str()).await?.is_some() {
+            return Err(ServerError::AlreadyExists(parameters.name.to_string()).into());
+        }
+
+        let volume = parameters.storage.source(&parameters.name);
+
+        if copy_from.is_some() && self.volume_exists(&volume).await? {
+            return Err(ServerError::VolumeExists(volume).into());
+        }
+
+        let server_container_name = format!("mayo-{}", parameters.name);
+
+        let is_in_the_way = *container_name == server_container_name;
+
+        let unmanaged_name = format!("{container_name}-unmanaged");
+
+        if *is_running {
+            self.stop_container(container_id).await?;
+        }
+
+        if is_in_the_way {
+            self
+                //
+                .engine
+                //
+                .rename_container(container_id, &unmanaged_name)
+                //
+                .await
+                //
+                .map_err(Error::engine("failed to rename the container"))?;
+        }
+
+        let result = async {
+            if let Some(from) = copy_from {
+                self.copy_volume(parameters, from)
+                    //
+                    .await
+                    //
+                    .map_err(|error| ServerError::DataVolume(Box::new(error)))?;
+            }
+
+            let id = self
+                //
+                .create_server_container(parameters.clone(), None, &server_container_name)
+                //
+                .await?;
+
+            let ready = async {
+                self
+                    //
+                    .engine
+                    //
+                    .start_container(&id)
+                    //
+                    .await
+                    //
+                    .map_err(Error::engine("failed to start the new container"))?;
+
+                self.wait_until_ready(&id, timeout)
+                    //
+                    .await
+            }
+            .await;
+
+            if ready.is_err() {
+                self.remove_container_after(&id, ready)
+                    //
+                    .await?;
+            }
+
+            Ok(id)
+        }
+        .await;
+
+        let id = match result {
+            Ok(id) => id,
+
+            Err(error) => {
+                // it did not exist before, and would make a retry fail
+                if copy_from.is_some()
+                    && let Storage::Volume = parameters.storage
+                    && let Err(removal_error) = self.remove_copied_volume(&volume).await
+                {
+                    self.warn(Warning::VolumeLeft {
+                        volume: volume.clone(),
+
+                        error: Box::new(removal_error),
+                    });
+                }
+
+                if is_in_the_way {
+                    self
+                        //
+                        .engine
+                        //
+                        .rename_container(container_id, container_name)
+                        //
+                        .await
+                        //
+                        .map_err(Error::engine("failed to rename the previous container back"))?;
+                }
+
+                if *is_running {
+                    self
+                        //
+                        .engine
+                        //
+                        .start_container(container_id)
+                        //
+                        .await
+                        //
+                        .map_err(Error::engine("failed to restart the previous container"))?;
+                }
+
+                return Err(ServerError::Restored(Box::new(error)).into());
+            }
+        };
+
+        self.remove_container(container_id)
+            //
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Removes the volume [`Self::adopt`] copied the data of a container into, if the copy got as far as creating it.
+    async fn remove_copied_volume(&self, volume: &str) -> Result<()> {
+        if self.volume_exists(volume).await? {
+            self.remove_volume(volume)
+                //
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the contents of a volume into the data mount of a server that does not exist yet, through a short-lived
+    /// container.
+    pub(crate) async fn copy_volume(&self, parameters: &Parameters, from: &str) -> Result<()> {
+        let image_id = self
+            //
+            .version_to_image(&parameters.version)
+            //
+            .await?;
+
+        if let Storage::Volume = parameters.storage {
+            self.ensure_volume(&parameters.storage.source(&parameters.name)).await?;
+        }
+
+        let config = container::Config {
+            image: Some(image_id),
+
+            user: Some("0:0".to_string()),
+
+            entrypoint: Some(vec![
+                //
+                "cp".to_string(),
+                //
+                "-a".to_string(),
+                //
+                "/mayo/.source/.".to_string(),
+                //
+                MAYO_DATA_PATH.to_string(),
+            ]),
+
+            cmd: Some(vec![]),
+
+            host_config: Some(HostConfig {
+                // the data mount comes first, as everywhere else
+                mounts: Some(vec![
+                    Mount {
+                        target: Some(MAYO_DATA_PATH.to_string()),
+
+                        source: Some(parameters.storage.source(&parameters.name)),
+
+                        typ: Some(parameters.storage.mount_type()),
+
+                        read_only: Some(false),
+
+                        ..Default::default()
+                    },
+                    Mount {
+                        target: Some("/mayo/.source".to_string()),
+
+                        source: Some(from.to_string()),
+
+                        typ: Some(MountTypeEnum::VOLUME),
+
+                        read_only: Some(true),
+
+                        ..Default::default()
+                    },
+                ]),
+
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        };
+
+        let id = self
+            //
+            .engine
+            //
+            .create_container(None, config)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to create a container"))?;
+
+        let result = self.run_to_completion(&id).await;
+
+        self.remove_container_after(&id, result)
+            //
+            .await
+    }
+
+    /// Whether the data mount of a server that does not exist yet is empty, checked through a short-lived container so
+    /// that it works on remote hosts too.
+    pub(crate) async fn is_data_empty(&self, parameters: &Parameters) -> Result<bool> {
+        let command = format!("[ -z \"$(ls -A {MAYO_DATA_PATH})\" ]");
+
+        match self.run_on_data(parameters, &["sh", "-c", &command]).await {
+            Ok(()) => Ok(true),
+
+            Err(Error::Server(ServerError::HelperExited(1))) => Ok(false),
+
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Deletes everything in the data mount of a server that does not exist yet, through a short-lived container.
+    pub(crate) async fn clear_data(&self, parameters: &Parameters) -> Result<()> {
+        self.run_on_data(parameters, &["find", MAYO_DATA_PATH, "-mindepth", "1", "-delete"])
+            //
+            .await
+    }
+
+    /// Runs a command as root in a short-lived container that only mounts the data of a server.
+    async fn run_on_data(&self, parameters: &Parameters, entrypoint: &[&str]) -> Result<()> {
+        let image_id = self
+            //
+            .version_to_image(&parameters.version)
+            //
+            .await?;
+
+        let config = container::Config {
+            image: Some(image_id),
+
+            user: Some("0:0".to_string()),
+
+            entrypoint: Some(entrypoint.iter().map(ToString::to_string).collect()),
+
+            cmd: Some(vec![]),
+
+            host_config: Some(HostConfig {
+                mounts: Some(vec![Mount {
+                    target: Some(MAYO_DATA_PATH.to_string()),
+
+                    source: Some(parameters.storage.source(&parameters.name)),
+
+                    typ: Some(parameters.storage.mount_type()),
+
+                    read_only: Some(false),
+
+                    ..Default::default()
+                }]),
+
+                ..Default::default()
+            }),
+
+            ..Default::default()
+        };
+
+        let id = self
+            //
+            .engine
+            //
+            .create_container(None, config)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to create a container"))?;
+
+        let result = self.run_to_completion(&id).await;
+
+        self.remove_container_after(&id, result)
+            //
+            .await
+    }
+
+    pub async fn find_server(&self, name: &str) -> Result<Option<Server>> {
+        let server = self
+            //
+            .list_servers()
+            //
+            .await?
+            //
+            .into_iter()
+            //
+            .find(|server| server.parameters.name.as_str() == name);
+
+        Ok(server)
+    }
+
+    pub async fn list_servers(&self) -> Result<Vec<Server>> {
+        let (servers, _) = self.list_all_servers().await?;
 
         Ok(servers)
     }
+
+    /// Lists the servers this CLI can manage, and the containers with server labels that it cannot, with the reason.
+    pub async fn list_all_servers(&self) -> Result<(Vec<Server>, Vec<HiddenContainer>)> {
+        // filter out non-mayo containers
+        let summaries = self
+            //
+            .engine
+            //
+            .list_containers(LABEL_KEY_CLI_VERSION)
+            //
+            .await
+            //
+            .map_err(Error::engine("failed to list containers"))?;
+
+        let mut tags_by_image = HashMap::<String, Vec<String>>::default();
+
+        let mut servers = Vec::new();
+
+        let mut hidden = Vec::new();
+
+        for summary in summaries {
+            let result = Server::try_from(summary.clone());
+
+            match result {
+                // a newer CLI may run app versions this one does not know, which is fine for a server it only shows
+                Ok(server) if server.is_read_only() => {
+                    servers.push(server);
+                }
+
+                Ok(server) => {
+                    if let Some(reason) = self.hidden_reason(&summary, &mut tags_by_image).await? {
+                        hidden.push(HiddenContainer::new(&summary, reason));
+
+                        continue;
+                    }
+
+                    servers.push(server);
+                }
+
+                Err(ServerError::MalformedLabel {
+                    source: ParametersError::NewerSchema(schema),
+                    ..
+                }) => {
+                    hidden.push(HiddenContainer::new(&summary, HiddenReason::NewerSchema(schema)));
+                }
+
+                Err(error) => {
+                    hidden.push(HiddenContainer::new(&summary, HiddenReason::CorruptParameters(error)));
+                }
+            }
+        }
+
+        Ok((servers, hidden))
+    }
+
+    /// Tells why the container of a readable server cannot be managed, looking up the tags of its image once per image.
+    ///
+    /// Which CLI created the container does not matter, the schema of its label decides whether it can be read.
+    async fn hidden_reason(
+        &self,
+        summary: &ContainerSummary,
+        tags_by_image: &mut HashMap<String, Vec<String>>,
+    ) -> Result<Option<HiddenReason>> {
+        let ContainerSummary { image_id, .. } = summary;
+
+        // containers with incompatible app version
+        let Some(image_id) = image_id else {
+            return Ok(Some(HiddenReason::UntaggedImage));
+        };
+
+        if !tags_by_image.contains_key(image_id) {
+            let tags = match self.engine.inspect_image(image_id).await {
+                Ok(ImageInspect { repo_tags, .. }) => repo_tags.unwrap_or_default(),
+
+                // an image removed by force leaves its containers behind
+                Err(EngineError::NotFound(_)) => Vec::new(),
+
+                Err(source) => {
+                    return Err(Error::Engine {
+                        context: "failed to inspect an image".to_string(),
+
+                        source,
+                    });
+                }
+            };
+
+            tags_by_image.insert(image_id.clone(), tags);
+        }
+
+        let repo_tags = &tags_by_image[image_id];
+
+        let tags = repo_tags
+            //
+            .iter()
+            //
+            .flat_map(|repo_tag| {
+                repo_tag
+                    //
+                    .split_once(':')
+                    //
+                    .map(|(_, tag)| tag)
+            })
+            //
+            .collect::<Vec<_>>();
+
+        if tags.is_empty() {
+            return Ok(Some(HiddenReason::UntaggedImage));
+        }
+
+        let has_compatible_version = versioning::tags_to_compatible_app_versions(tags.iter().copied())
+            //
+            .next()
+            //
+            .is_some();
+
+        if !has_compatible_version {
+            return Ok(Some(HiddenReason::IncompatibleImage(repo_tags.clone())));
+        }
+
+        Ok(None)
+    }
+}
+
+/// The configuration of a server's container, as [`Mayo::create_server`] creates it from `image`.
+pub(crate) fn server_container_config(
+    parameters: Parameters,
+    origin: Option<&str>,
+    image: String,
+    restart_policy: RestartPolicyNameEnum,
+) -> container::Config<String> {
+    let mut labels = HashMap::<String, String>::default();
+
+    labels.insert(
+        //
+        LABEL_KEY_CLI_VERSION.to_string(),
+        //
+        versioning::current_cli_version().to_string(),
+    );
+
+    labels.insert(
+        //
+        LABEL_KEY_PARAMETERS.to_string(),
+        //
+        encode_parameters(&parameters),
+    );
+
+    if let Some(origin) = origin {
+        labels.insert(
+            //
+            LABEL_KEY_ORIGIN.to_string(),
+            //
+            origin.to_string(),
+        );
+    }
+
+    let Parameters {
+        name,
+
+        version: _,
+
+        port,
+
+        authentication,
+
+        tls,
+
+        security,
+
+        storage,
+
+        libraries,
+
+        environment,
+
+        acme: _,
+    } = parameters;
+
+    let healthcheck = health_check(&port, tls.is_enabled());
+
+    let mut env = vec![
+        //
+        format!("BUN_PORT={port}"),
+        //
+        format!("MAYO_DATA_PATH={MAYO_DATA_PATH}"),
+    ];
+
+    if let Some((crt, key)) = tls.into_inner() {
+        env.extend_from_slice(&[
+            //
+            format!("MAYO_TLS_CRT={crt}"),
+            //
+            format!("MAYO_TLS_KEY={key}"),
+        ])
+    }
+
+    if authentication.is_required() {
+        env.push("MAYO_AUTHENTICATION=required".to_string());
+    }
+
+    env.extend(environment.to_pairs());
+
+    let mut mounts = vec![Mount {
+        target: Some(MAYO_DATA_PATH.to_string()),
+
+        source: Some(storage.source(&name)),
+
+        typ: Some(storage.mount_type()),
+
+        read_only: Some(false),
+
+        ..Default::default()
+    }];
+
+    if !libraries.is_empty() {
+        env.push(format!("MAYO_LIBRARY_PATHS={}", libraries.targets().join(":")));
+
+        mounts.extend(libraries.iter().map(|library| Mount {
+            target: Some(library.target()),
+
+            source: Some(library.path.display().to_string()),
+
+            typ: Some(MountTypeEnum::BIND),
+
+            read_only: Some(true),
+
+            ..Default::default()
+        }));
+    }
+
+    let mut host_config = HostConfig {
+        mounts: Some(mounts),
+
+        network_mode: Some("host".to_string()),
+
+        restart_policy: Some(RestartPolicy {
+            name: Some(restart_policy),
+
+            maximum_retry_count: None,
+        }),
+
+        ..Default::default()
+    };
+
+    let mut user = None;
+
+    if security.is_hardened() {
+        host_config.cap_drop = Some(vec!["ALL".to_string()]);
+
+        host_config.security_opt = Some(vec!["no-new-privileges:true".to_string()]);
+
+        host_config.readonly_rootfs = Some(true);
+
+        host_config.tmpfs = Some(HashMap::from([(
+            //
+            "/tmp".to_string(),
+            //
+            "rw,noexec,nosuid,nodev,size=64m".to_string(),
+        )]));
+
+        user = Some(HARDENED_USER.to_string());
+    }
+
+    container::Config {
+        image: Some(image),
+
+        user,
+
+        env: Some(env),
+
+        labels: Some(labels),
+
+        healthcheck: Some(healthcheck),
+
+        host_config: Some(host_config),
+
+        ..Default::default()
+    }
+}
+
+/// Whether a server's data volume is handed over to the hardened profile's user before the server is created.
+fn prepares_data_volume(parameters: &Parameters) -> bool {
+    // bind mounts are checked for the right ownership instead, since they belong to the host
+    parameters.security.is_hardened() && matches!(parameters.storage, Storage::Volume)
+}
+
+/// The configuration of the container that [`Mayo::prepare_data_volume`] runs before a hardened server's.
+pub(crate) fn data_volume_preparation_config(
+    image: String,
+    server_config: &container::Config<String>,
+) -> container::Config<String> {
+    container::Config {
+        image: Some(image),
+
+        user: Some("0:0".to_string()),
+
+        entrypoint: Some(vec![
+            //
+            "chown".to_string(),
+            //
+            "-R".to_string(),
+            //
+            HARDENED_USER.to_string(),
+            //
+            MAYO_DATA_PATH.to_string(),
+        ]),
+
+        cmd: Some(vec![]),
+
+        host_config: Some(HostConfig {
+            // the data mount always comes first
+            mounts: server_config
+                //
+                .host_config
+                //
+                .as_ref()
+                //
+                .and_then(|host_config| host_config.mounts.as_ref())
+                //
+                .map(|mounts| mounts.iter().take(1).cloned().collect()),
+
+            ..Default::default()
+        }),
+
+        ..Default::default()
+    }
+}
+
+fn health_check(port: &Port, tls: bool) -> HealthConfig {
+    const SECOND: i64 = 1_000_000_000;
+
+    let scheme = if tls { "https" } else { "http" };
+
+    // the app image ships bun but not necessarily curl or wget, so the probe is written in javascript.
+    // any response below 500 counts as healthy, since authentication may legitimately answer with 401.
+    let script = format!(
+        //
+        "fetch('{scheme}://localhost:{port}/', {{ tls: {{ rejectUnauthorized: false }} }})\
+         .then((response) => process.exit(response.status < 500 ? 0 : 1), () => process.exit(1))"
+    );
+
+    HealthConfig {
+        test: Some(vec![
+            //
+            "CMD".to_string(),
+            //
+            "bun".to_string(),
+            //
+            "--eval".to_string(),
+            //
+            script,
+        ]),
+
+        interval: Some(5 * SECOND),
+
+        timeout: Some(3 * SECOND),
+
+        retries: Some(3),
+
+        start_period: Some(30 * SECOND),
+
+        ..Default::default()
+    }
+}
+
+// e.g. ghcr.io/mayo-dayo/app:0.2.0
+fn image_reference(version: &Version) -> String {
+    format!(
+        //
+        "{}/{}/{}:{}",
+        //
+        registry::REGISTRY,
+        //
+        registry::USERNAME,
+        //
+        registry::REPOSITORY,
+        //
+        version
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::MemoryEngine;
+    use crate::parameters::Authentication;
+    use crate::parameters::Environment;
+    use crate::parameters::Libraries;
+    use crate::parameters::Security;
+    use crate::parameters::Tls;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn mayo() -> Mayo<MemoryEngine> {
+        Mayo::with_engine(MemoryEngine::default(), "memory", true)
+    }
+
+    fn parameters(port: u16) -> Parameters {
+        Parameters {
+            name: Name::try_new("music".to_string()).unwrap(),
+
+            version: Version::new(0, 3, 0),
+
+            port: Port::new(port),
+
+            authentication: Authentication::new(true),
+
+            tls: Tls::disabled(),
+
+            security: Security::Hardened,
+
+            storage: Storage::Volume,
+
+            libraries: Libraries::default(),
+
+            environment: Environment::default(),
+
+            acme: None,
+        }
+    }
+
+    async fn only_server(mayo: &Mayo<MemoryEngine>) -> Server {
+        let mut servers = mayo.list_servers().await.unwrap();
+
+        assert_eq!(servers.len(), 1, "expected a single server, got {servers:?}");
+
+        servers.remove(0)
+    }
+
+    #[tokio::test]
+    async fn create_server_starts_a_listed_server() {
+        let mayo = mayo();
+
+        let id = mayo.create_server(parameters(8443), None).await.unwrap();
+
+        let server = only_server(&mayo).await;
+
+        assert_eq!(server.id, id);
+
+        assert_eq!(server.state, "running");
+
+        assert_eq!(server.parameters.name.to_string(), "music");
+
+        assert_eq!(server.parameters.port.get(), 8443);
+
+        assert!(mayo.volume_exists("mayo-music").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn create_server_refuses_a_taken_name() {
+        let mayo = mayo();
+
+        mayo.create_server(parameters(8443), None).await.unwrap();
+
+        assert!(mayo.create_server(parameters(8444), None).await.is_err());
+
+        assert_eq!(only_server(&mayo).await.parameters.port.get(), 8443);
+    }
+
+    #[tokio::test]
+    async fn list_servers_is_empty_without_servers() {
+        assert!(mayo().list_servers().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn replace_server_swaps_the_container() {
+        let mayo = mayo();
+
+        let old_id = mayo.create_server(parameters(8443), None).await.unwrap();
+
+        let server = only_server(&mayo).await;
+
+        let new_id = mayo.replace_server(&server, parameters(8444), TIMEOUT).await.unwrap();
+
+        assert_ne!(new_id, old_id);
+
+        let server = only_server(&mayo).await;
+
+        assert_eq!(server.id, new_id);
+
+        assert_eq!(server.state, "running");
+
+        assert_eq!(server.parameters.port.get(), 8444);
+
+        // the new container takes over the name of the old one
+        let named_id = mayo
+            //
+            .engine
+            //
+            .inspect_container("mayo-music")
+            //
+            .await
+            //
+            .unwrap()
+            //
+            .id;
+
+        assert_eq!(named_id, Some(new_id));
+    }
+
+    #[tokio::test]
+    async fn replace_server_restores_the_previous_container_on_failure() {
+        let mayo = mayo();
+
+        let old_id = mayo.create_server(parameters(8443), None).await.unwrap();
+
+        let server = only_server(&mayo).await;
+
+        mayo.engine.crash_on_start("mayo-music-next", 1);
+
+        let result = mayo.replace_server(&server, parameters(8444), TIMEOUT).await;
+
+        assert!(
+            matches!(result, Err(Error::Server(ServerError::Restored(_)))),
+            "expected the previous container to be restored, got {result:?}"
+        );
+
+        let server = only_server(&mayo).await;
+
+        assert_eq!(server.id, old_id);
+
+        assert_eq!(server.state, "running");
+
+        assert_eq!(server.parameters.port.get(), 8443);
+
+        // the failed container is gone, so that the next attempt can use its name
+        assert!(mayo.engine.inspect_container("mayo-music-next").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn remove_server_keeps_the_data_unless_asked() {
+        let mayo = mayo();
+
+        mayo.create_server(parameters(8443), None).await.unwrap();
+
+        mayo.remove_server(&only_server(&mayo).await, false).await.unwrap();
+
+        assert!(mayo.list_servers().await.unwrap().is_empty());
+
+        assert!(mayo.volume_exists("mayo-music").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn remove_server_removes_the_data() {
+        let mayo = mayo();
+
+        mayo.create_server(parameters(8443), None).await.unwrap();
+
+        mayo.remove_server(&only_server(&mayo).await, true).await.unwrap();
+
+        assert!(mayo.list_servers().await.unwrap().is_empty());
+
+        assert!(!mayo.volume_exists("mayo-music").await.unwrap());
+    }
 }
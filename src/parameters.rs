@@ -1,13 +1,27 @@
+use crate::acme;
+use crate::acme::Acme;
+use crate::certificate;
+use crate::error;
+use crate::error::AcmeError;
+use crate::error::FileError;
+use crate::error::ParametersError;
+use crate::error::Warning;
+use crate::mayo;
 use crate::versioning;
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
 
-use anyhow::*;
+use bollard::models::MountTypeEnum;
 
 use inquire::Confirm;
 use inquire::CustomType;
+use inquire::Select;
 use inquire::Text;
 use inquire::error::CustomUserError;
 use inquire::validator::Validation;
@@ -32,18 +46,37 @@ pub struct Parameters {
     pub authentication: Authentication,
 
     pub tls: Tls,
+
+    // servers created before security profiles existed run with docker's defaults
+    #[serde(default)]
+    pub security: Security,
+
+    #[serde(default)]
+    pub storage: Storage,
+
+    #[serde(default)]
+    pub libraries: Libraries,
+
+    #[serde(default)]
+    pub environment: Environment,
+
+    /// Set when the certificate was obtained through ACME, so that it can be renewed.
+    #[serde(default)]
+    pub acme: Option<Acme>,
 }
 
 impl<'a> TryFrom<&'a str> for Parameters {
-    type Error = ();
+    type Error = ParametersError;
 
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        serde_json::from_str::<Parameters>(value).map_err(|_| ())
+        Ok(serde_json::from_str::<Parameters>(value)?)
     }
 }
 
 impl Parameters {
-    pub async fn inquire() -> Result<Option<Self>> {
+    /// `is_local` tells whether the server runs on this machine, which some of the choices depend on. Warnings about
+    /// the choices, e.g. about the certificate, are handed to `on_warning`.
+    pub async fn inquire(is_local: bool, on_warning: &dyn Fn(Warning)) -> Result<Option<Self>, ParametersError> {
         let Some(port) = Port::inquire()?
         //
         else {
@@ -56,7 +89,25 @@ impl Parameters {
             return Ok(None);
         };
 
-        let Some(tls) = Tls::inquire()?
+        let Some((tls, acme)) = Tls::inquire(is_local, on_warning).await?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(security) = Security::inquire()?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(storage) = Storage::inquire(is_local)?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let Some(libraries) = Libraries::inquire(is_local)?
         //
         else {
             return Ok(None);
@@ -66,7 +117,7 @@ impl Parameters {
             //
             .await
             //
-            .context("failed to get the latest compatible version")?;
+            .map_err(ParametersError::LatestVersion)?;
 
         let name = Name::generate();
 
@@ -80,11 +131,55 @@ impl Parameters {
             authentication,
 
             tls,
+
+            security,
+
+            storage,
+
+            libraries,
+
+            environment: Environment::default(),
+
+            acme,
         }))
     }
 
-    pub fn encode_for_label(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    /// A copy that can be stored outside the container: private keys and secret values are emptied.
+    pub fn without_secrets(&self) -> Self {
+        let mut result = self.clone();
+
+        result.tls = self.tls.without_key();
+
+        result.environment = self.environment.without_secret_values();
+
+        result
+    }
+
+    /// Secrets emptied by [`without_secrets`](Self::without_secrets) and not filled again, `MAYO_TLS_KEY` for the key.
+    pub fn missing_secrets(&self) -> Vec<String> {
+        let key = self
+            //
+            .tls
+            //
+            .0
+            //
+            .as_ref()
+            //
+            .filter(|(_, key)| key.is_empty())
+            //
+            .map(|_| "MAYO_TLS_KEY".to_string());
+
+        let variables = self
+            //
+            .environment
+            //
+            .iter()
+            //
+            .filter(|(_, variable)| variable.secret && variable.value.is_empty())
+            //
+            .map(|(key, _)| key.clone());
+
+        key.into_iter().chain(variables).collect()
     }
 }
 
@@ -109,7 +204,7 @@ impl Ord for Name {
 
 impl PartialOrd for Name {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -120,6 +215,26 @@ impl fmt::Display for Name {
 }
 
 impl Name {
+    pub fn try_new(name: String) -> Result<Self, ParametersError> {
+        let is_valid = !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                //
+                .chars()
+                //
+                .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-');
+
+        if !is_valid {
+            return Err(ParametersError::InvalidName(name));
+        }
+
+        Ok(Self(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     pub fn generate() -> Self {
         let name = Generator::default()
             //
@@ -141,14 +256,22 @@ impl fmt::Display for Port {
 }
 
 impl Port {
-    pub fn inquire() -> Result<Option<Self>> {
+    pub fn new(port: u16) -> Self {
+        Self(port)
+    }
+
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+
+    pub fn inquire() -> Result<Option<Self>, ParametersError> {
         CustomType::<u16>::new("Which port would you like the server to use?")
             //
             .with_default(8080)
             //
             .prompt_skippable()
             //
-            .context("failed to inquire the port")
+            .map_err(ParametersError::prompt("port"))
             //
             .map(|option| {
                 option
@@ -172,7 +295,7 @@ impl fmt::Display for Authentication {
 }
 
 impl Authentication {
-    pub fn inquire() -> Result<Option<Self>> {
+    pub fn inquire() -> Result<Option<Self>, ParametersError> {
         Confirm::new("Would you like to disable mandatory authentication?")
             //
             .with_default(false)
@@ -181,7 +304,7 @@ impl Authentication {
             //
             .prompt_skippable()
             //
-            .context("failed to inquire the authentication")
+            .map_err(ParametersError::prompt("authentication"))
             //
             .map(|option| {
                 option
@@ -190,11 +313,645 @@ impl Authentication {
             })
     }
 
+    pub fn new(is_required: bool) -> Self {
+        Self(is_required)
+    }
+
     pub fn is_required(&self) -> bool {
         self.0
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Docker's default capabilities, a writable root filesystem and the image's user.
+    #[default]
+    Standard,
+
+    /// No capabilities, no privilege escalation, a read-only root filesystem and an unprivileged user.
+    Hardened,
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard => f.write_str("standard"),
+
+            Self::Hardened => f.write_str("hardened"),
+        }
+    }
+}
+
+impl Security {
+    pub fn inquire() -> Result<Option<Self>, ParametersError> {
+        Confirm::new("Would you like to use the hardened security profile?")
+            //
+            .with_default(true)
+            //
+            .with_help_message(
+                "The server will run without capabilities, as an unprivileged user, on a read-only root filesystem.",
+            )
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("security profile"))
+            //
+            .map(|option| {
+                option
+                    //
+                    .map(|value| if value { Self::Hardened } else { Self::Standard })
+            })
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        matches!(self, Self::Hardened)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// A named volume called `mayo-{name}`.
+    #[default]
+    Volume,
+
+    /// A directory on the host.
+    Bind(PathBuf),
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Volume => f.write_str("volume"),
+
+            Self::Bind(_) => f.write_str("bind mount"),
+        }
+    }
+}
+
+impl Storage {
+    /// The directory is only looked at if `is_local`, since a remote daemon's paths are not on this machine.
+    pub fn inquire(is_local: bool) -> Result<Option<Self>, ParametersError> {
+        let validator = move |input: &str| -> Result<Validation, CustomUserError> {
+            if input.is_empty() {
+                return Ok(Validation::Valid);
+            }
+
+            let path = Path::new(input);
+
+            Ok(if !path.is_absolute() {
+                Validation::Invalid("Not an absolute path 🧭".into())
+            } else if is_local && !path.is_dir() {
+                Validation::Invalid("Not a directory 📂".into())
+            } else {
+                Validation::Valid
+            })
+        };
+
+        Text::new("Where would you like to store the data?")
+            //
+            .with_help_message("Enter an absolute path to a host directory, or leave empty to use a named volume.")
+            //
+            .with_validator(validator)
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("data location"))
+            //
+            .map(|option| {
+                option
+                    //
+                    .map(|input| {
+                        if input.is_empty() {
+                            Self::Volume
+                        } else {
+                            Self::Bind(PathBuf::from(input))
+                        }
+                    })
+            })
+    }
+
+    /// The volume name or host path that is mounted at the data path.
+    pub fn source(&self, name: &Name) -> String {
+        match self {
+            Self::Volume => format!("mayo-{name}"),
+
+            Self::Bind(path) => path.display().to_string(),
+        }
+    }
+
+    pub fn mount_type(&self) -> MountTypeEnum {
+        match self {
+            Self::Volume => MountTypeEnum::VOLUME,
+
+            Self::Bind(_) => MountTypeEnum::BIND,
+        }
+    }
+
+    /// Makes sure a bind-mounted directory exists and is writable by the user the server will run as.
+    ///
+    /// On a `rootless` engine, the hardened profile's user is a subordinate UID of the host, so who owns the directory
+    /// is not checked.
+    pub fn check(&self, security: Security, rootless: bool) -> Result<(), ParametersError> {
+        let Self::Bind(path) = self else {
+            return Ok(());
+        };
+
+        if !path.is_absolute() {
+            return Err(ParametersError::NotAbsolute(path.clone()));
+        }
+
+        let metadata = fs::metadata(path)
+            //
+            .map_err(FileError::on("read the metadata of", path))?;
+
+        if !metadata.is_dir() {
+            return Err(ParametersError::NotADirectory(path.clone()));
+        }
+
+        if security.is_hardened() && !rootless {
+            const OTHERS_WRITE_EXECUTE: u32 = 0o003;
+
+            const OWNER_WRITE_EXECUTE: u32 = 0o300;
+
+            let mode = metadata.mode();
+
+            let is_writable = if metadata.uid() == mayo::HARDENED_UID {
+                mode & OWNER_WRITE_EXECUTE == OWNER_WRITE_EXECUTE
+            } else {
+                mode & OTHERS_WRITE_EXECUTE == OTHERS_WRITE_EXECUTE
+            };
+
+            if !is_writable {
+                return Err(ParametersError::NotWritable {
+                    path: path.clone(),
+
+                    user: mayo::HARDENED_USER,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Host directories mounted read-only into the server, e.g. music collections.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Libraries(Vec<Library>);
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Library {
+    pub name: String,
+
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Libraries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("none");
+        }
+
+        let libraries = self
+            //
+            .0
+            //
+            .iter()
+            //
+            .map(|Library { name, path }| format!("{name}={}", path.display()))
+            //
+            .collect::<Vec<_>>();
+
+        f.write_str(&libraries.join(", "))
+    }
+}
+
+impl Library {
+    pub fn try_new(name: String, path: PathBuf) -> Result<Self, ParametersError> {
+        let is_valid = !name.is_empty()
+            && name
+                //
+                .chars()
+                //
+                .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_');
+
+        if !is_valid {
+            return Err(ParametersError::InvalidLibraryName(name));
+        }
+
+        if !path.is_absolute() {
+            return Err(ParametersError::NotAbsolute(path.clone()));
+        }
+
+        Ok(Self { name, path })
+    }
+
+    /// Where the library is mounted inside the container.
+    pub fn target(&self) -> String {
+        format!("/mayo/libraries/{}", self.name)
+    }
+
+    /// Makes sure the directory exists and can be listed by the user the server will run as.
+    ///
+    /// On a `rootless` engine, only the former is checked, as for [`Storage::check`].
+    pub fn check(&self, security: Security, rootless: bool) -> Result<(), ParametersError> {
+        let Self { name, path } = self;
+
+        let metadata = fs::metadata(path)
+            //
+            .map_err(FileError::on("read the metadata of", path))?;
+
+        if !metadata.is_dir() {
+            return Err(ParametersError::NotADirectory(path.clone()));
+        }
+
+        fs::read_dir(path)
+            //
+            .map_err(|source| ParametersError::UnreadableLibrary {
+                name: name.clone(),
+
+                path: path.clone(),
+
+                source,
+            })?;
+
+        if security.is_hardened() && !rootless {
+            const OTHERS_READ_EXECUTE: u32 = 0o005;
+
+            const OWNER_READ_EXECUTE: u32 = 0o500;
+
+            let mode = metadata.mode();
+
+            let is_readable = if metadata.uid() == mayo::HARDENED_UID {
+                mode & OWNER_READ_EXECUTE == OWNER_READ_EXECUTE
+            } else {
+                mode & OTHERS_READ_EXECUTE == OTHERS_READ_EXECUTE
+            };
+
+            if !is_readable {
+                return Err(ParametersError::NotReadable(path.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Libraries {
+    /// The directories are only looked at if `is_local`, since a remote daemon's paths are not on this machine.
+    pub fn inquire(is_local: bool) -> Result<Option<Self>, ParametersError> {
+        let validator = move |input: &str| -> Result<Validation, CustomUserError> {
+            if input.is_empty() {
+                return Ok(Validation::Valid);
+            }
+
+            let path = Path::new(input);
+
+            Ok(if !path.is_absolute() {
+                Validation::Invalid("Not an absolute path 🧭".into())
+            } else if is_local && fs::read_dir(path).is_err() {
+                Validation::Invalid("Not a readable directory 📂".into())
+            } else {
+                Validation::Valid
+            })
+        };
+
+        let mut result = Self::default();
+
+        loop {
+            let Some(input) = Text::new("Would you like to add a music library?")
+                //
+                .with_help_message("Enter an absolute path to a host directory, or leave empty to continue.")
+                //
+                .with_validator(validator)
+                //
+                .prompt_skippable()
+                //
+                .map_err(ParametersError::prompt("library path"))?
+            //
+            else {
+                return Ok(None);
+            };
+
+            if input.is_empty() {
+                return Ok(Some(result));
+            }
+
+            let path = PathBuf::from(input);
+
+            let default_name = path
+                //
+                .file_name()
+                //
+                .map(|name| name.to_string_lossy().to_string())
+                //
+                .unwrap_or_default();
+
+            let name_validator = {
+                let libraries = result.clone();
+
+                let path = path.clone();
+
+                move |input: &str| -> Result<Validation, CustomUserError> {
+                    let added = Library::try_new(input.to_string(), path.clone())
+                        //
+                        .and_then(|library| libraries.clone().add(library));
+
+                    Ok(match added {
+                        Ok(()) => Validation::Valid,
+
+                        Err(error) => Validation::Invalid(format!("{error} 😣").into()),
+                    })
+                }
+            };
+
+            let Some(name) = Text::new("What would you like to call this library?")
+                //
+                .with_default(&default_name)
+                //
+                .with_validator(name_validator)
+                //
+                .prompt_skippable()
+                //
+                .map_err(ParametersError::prompt("library name"))?
+            //
+            else {
+                return Ok(None);
+            };
+
+            // checked by the validator already
+            result.add(Library::try_new(name, path)?)?;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Library> {
+        self.0.iter()
+    }
+
+    pub fn targets(&self) -> Vec<String> {
+        self.iter().map(Library::target).collect()
+    }
+
+    pub fn add(&mut self, library: Library) -> Result<(), ParametersError> {
+        if self.iter().any(|existing| existing.name == library.name) {
+            return Err(ParametersError::LibraryExists(library.name));
+        }
+
+        self.0.push(library);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Library, ParametersError> {
+        let index = self
+            //
+            .0
+            //
+            .iter()
+            //
+            .position(|library| library.name == name)
+            //
+            .ok_or_else(|| ParametersError::NoSuchLibrary(name.to_string()))?;
+
+        Ok(self.0.remove(index))
+    }
+
+    pub fn check(&self, security: Security, rootless: bool) -> Result<(), ParametersError> {
+        self.iter().try_for_each(|library| library.check(security, rootless))
+    }
+}
+
+/// Extra environment variables passed to the app as they are.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Environment(BTreeMap<String, Variable>);
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Variable {
+    pub value: String,
+
+    /// Secret values are never displayed.
+    pub secret: bool,
+}
+
+impl Environment {
+    /// Variables that the CLI sets itself and that therefore cannot be overridden.
+    pub const RESERVED_KEYS: [&str; 6] = [
+        "BUN_PORT",
+        "MAYO_DATA_PATH",
+        "MAYO_TLS_CRT",
+        "MAYO_TLS_KEY",
+        "MAYO_AUTHENTICATION",
+        "MAYO_LIBRARY_PATHS",
+    ];
+
+    /// Builds the environment from `KEY=VALUE` pairs and env files, marking the given keys as secret.
+    ///
+    /// Env files contain one `KEY=VALUE` pair per line; empty lines and lines starting with `#` are ignored. Pairs
+    /// given directly take precedence over the ones from files.
+    pub fn try_new(pairs: &[String], files: &[PathBuf], secrets: &[String]) -> Result<Self, ParametersError> {
+        let mut result = Self::default();
+
+        for file in files {
+            let contents = fs::read_to_string(file)
+                //
+                .map_err(FileError::on("read", file))?;
+
+            for (index, line) in contents.lines().enumerate() {
+                let line = line.trim();
+
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                result
+                    //
+                    .insert_pair(line)
+                    //
+                    .map_err(|source| ParametersError::InvalidLine {
+                        line: index + 1,
+
+                        path: file.clone(),
+
+                        source: Box::new(source),
+                    })?;
+            }
+        }
+
+        for pair in pairs {
+            result.insert_pair(pair)?;
+        }
+
+        for key in secrets {
+            let variable = result
+                //
+                .0
+                //
+                .get_mut(key)
+                //
+                .ok_or_else(|| ParametersError::SecretWithoutValue(key.clone()))?;
+
+            variable.secret = true;
+        }
+
+        Ok(result)
+    }
+
+    pub fn insert_pair(&mut self, pair: &str) -> Result<(), ParametersError> {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(ParametersError::InvalidPair(pair.to_string()));
+        };
+
+        let key = key.trim();
+
+        let is_valid = !key.is_empty()
+            && !key.starts_with(|char: char| char.is_ascii_digit())
+            && key
+                //
+                .chars()
+                //
+                .all(|char| char.is_ascii_alphanumeric() || char == '_');
+
+        if !is_valid {
+            return Err(ParametersError::InvalidVariableName(key.to_string()));
+        }
+
+        if Self::RESERVED_KEYS.contains(&key) {
+            return Err(ParametersError::ReservedVariable(key.to_string()));
+        }
+
+        self.0.insert(
+            //
+            key.to_string(),
+            //
+            Variable {
+                value: value.to_string(),
+
+                secret: false,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn without_secret_values(&self) -> Self {
+        let mut result = self.clone();
+
+        for variable in result.0.values_mut() {
+            if variable.secret {
+                variable.value.clear();
+            }
+        }
+
+        result
+    }
+
+    /// Fills the values of secrets that were removed, e.g. from a backup, and drops the ones `values` has no value for.
+    ///
+    /// Returns the keys of the dropped secrets.
+    pub fn fill_secret_values(&mut self, values: &Self) -> Vec<String> {
+        let mut dropped = Vec::new();
+
+        self.0.retain(|key, variable| {
+            if !variable.secret || !variable.value.is_empty() {
+                return true;
+            }
+
+            match values.0.get(key) {
+                Some(Variable { value, .. }) => {
+                    variable.value = value.clone();
+
+                    true
+                }
+
+                None => {
+                    dropped.push(key.clone());
+
+                    false
+                }
+            }
+        });
+
+        dropped
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Variable)> {
+        self.0.iter()
+    }
+
+    /// `KEY=VALUE` pairs as docker expects them.
+    pub fn to_pairs(&self) -> Vec<String> {
+        self
+            //
+            .iter()
+            //
+            .map(|(key, Variable { value, .. })| format!("{key}={value}"))
+            //
+            .collect()
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("none");
+        }
+
+        let pairs = self
+            //
+            .iter()
+            //
+            .map(|(key, Variable { value, secret })| {
+                if *secret {
+                    format!("{key}=<redacted>")
+                } else {
+                    format!("{key}={value}")
+                }
+            })
+            //
+            .collect::<Vec<_>>();
+
+        f.write_str(&pairs.join("\n"))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TlsSource {
+    Files,
+
+    SelfSigned,
+
+    LocalCa,
+
+    Acme,
+}
+
+impl fmt::Display for TlsSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Files => f.write_str("Existing certificate and private key files"),
+
+            Self::SelfSigned => f.write_str("Generate a self-signed certificate"),
+
+            Self::LocalCa => f.write_str("Generate a certificate signed by the local CA"),
+
+            Self::Acme => f.write_str("Obtain a certificate through ACME, e.g. from Let's Encrypt"),
+        }
+    }
+}
+
+impl TlsSource {
+    const ALL: [Self; 4] = [Self::Files, Self::SelfSigned, Self::LocalCa, Self::Acme];
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Tls(Option<(String, String)>);
 
@@ -209,127 +966,263 @@ impl fmt::Display for Tls {
 }
 
 impl Tls {
-    pub fn inquire() -> Result<Option<Self>> {
+    /// Also returns the ACME settings when the certificate was obtained that way.
+    pub async fn inquire(
+        is_local: bool,
+        on_warning: &dyn Fn(Warning),
+    ) -> Result<Option<(Self, Option<Acme>)>, ParametersError> {
         let Some(confirmed) = Confirm::new("Would you like to use TLS?")
             //
             .with_default(true)
             //
             .prompt_skippable()
             //
-            .context("failed to inquire the TLS confirmation")?
+            .map_err(ParametersError::prompt("TLS confirmation"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        if !confirmed {
+            return Ok(Some((Self(None), None)));
+        }
+
+        let Some(source) = Select::new("Where should the certificate come from?", TlsSource::ALL.to_vec())
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("certificate source"))?
         //
         else {
             return Ok(None);
         };
 
-        let mut result = Self(None);
+        let result = match source {
+            TlsSource::Files => Self::inquire_files(on_warning)?,
+
+            TlsSource::SelfSigned => Self::inquire_generated(false)?,
+
+            TlsSource::LocalCa => Self::inquire_generated(true)?,
+
+            TlsSource::Acme => {
+                let Some(acme) = Acme::inquire(is_local)? else {
+                    return Ok(None);
+                };
+
+                let (crt, key) = acme::obtain(&acme, on_warning)
+                    //
+                    .await
+                    //
+                    .map_err(AcmeError::step("failed to obtain a certificate through ACME"))?;
+
+                return Ok(Some((Self(Some((crt, key))), Some(acme))));
+            }
+        };
+
+        Ok(result.map(|tls| (tls, None)))
+    }
 
-        if confirmed {
-            let crt_validator = |input: &str| -> ::std::result::Result<Validation, CustomUserError> {
-                ::std::result::Result::Ok(
-                    fs::read(input)
+    fn inquire_files(on_warning: &dyn Fn(Warning)) -> Result<Option<Self>, ParametersError> {
+        let crt_validator = |input: &str| -> Result<Validation, CustomUserError> {
+            Ok(fs::read(input)
+                //
+                .map(|bytes| {
+                    rustls_pemfile::read_one_from_slice(&bytes)
                         //
-                        .map(|bytes| {
-                            rustls_pemfile::read_one_from_slice(&bytes)
+                        .map(|item| {
+                            if let Some((
                                 //
-                                .map(|item| {
-                                    if let Some((
-                                        //
-                                        X509Certificate(_),
-                                        //
-                                        _,
-                                    )) = item
-                                    {
-                                        Validation::Valid
-                                    } else {
-                                        Validation::Invalid("Not a certificate 😣".into())
-                                    }
-                                })
+                                X509Certificate(_),
                                 //
-                                .unwrap_or_else(|_| {
+                                _,
+                            )) = item
+                            {
+                                // the first item only tells the file apart from a key, the chain needs a closer look
+                                certificate::check_chain(&String::from_utf8_lossy(&bytes))
+                                    //
+                                    .map(|_| Validation::Valid)
                                     //
-                                    Validation::Invalid("Not a PEM file 😵".into())
-                                })
+                                    .unwrap_or_else(|error| {
+                                        Validation::Invalid(format!("{} 😣", error::describe(&error)).into())
+                                    })
+                            } else {
+                                Validation::Invalid("Not a certificate 😣".into())
+                            }
                         })
                         //
                         .unwrap_or_else(|_| {
                             //
-                            Validation::Invalid("Failed read from this file 😵‍💫".into())
-                        }),
-                )
-            };
-
-            let Some(crt) = Text::new("Please enter the path to your certificate:")
-                //
-                .with_validator(crt_validator)
-                //
-                .prompt_skippable()
+                            Validation::Invalid("Not a PEM file 😵".into())
+                        })
+                })
                 //
-                .context("failed to inquire the tls certificate path")?
+                .unwrap_or_else(|_| {
+                    //
+                    Validation::Invalid("Failed read from this file 😵‍💫".into())
+                }))
+        };
+
+        let Some(crt) = Text::new("Please enter the path to your certificate:")
             //
-            else {
-                return Ok(None);
-            };
+            .with_validator(crt_validator)
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("tls certificate path"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let crt = fs::read_to_string(&crt)
+            //
+            .map_err(FileError::on("read", &crt))?;
+
+        let chain = crt.clone();
 
-            let key_validator = |input: &str| -> ::std::result::Result<Validation, CustomUserError> {
-                ::std::result::Result::Ok(
-                    fs::read(input)
+        let key_validator = move |input: &str| -> Result<Validation, CustomUserError> {
+            Ok(fs::read(input)
+                //
+                .map(|bytes| {
+                    rustls_pemfile::read_one_from_slice(&bytes)
                         //
-                        .map(|bytes| {
-                            rustls_pemfile::read_one_from_slice(&bytes)
+                        .map(|item| {
+                            if let Some((
                                 //
-                                .map(|item| {
-                                    if let Some((
-                                        //
-                                        Pkcs1Key(_) | Pkcs8Key(_) | Sec1Key(_),
-                                        //
-                                        _,
-                                    )) = item
-                                    {
-                                        Validation::Valid
-                                    } else {
-                                        Validation::Invalid("Not a private key 😣".into())
-                                    }
-                                })
+                                Pkcs1Key(_) | Pkcs8Key(_) | Sec1Key(_),
                                 //
-                                .unwrap_or_else(|_| {
+                                _,
+                            )) = item
+                            {
+                                certificate::check_pair(&chain, &String::from_utf8_lossy(&bytes))
+                                    //
+                                    .map(|_| Validation::Valid)
                                     //
-                                    Validation::Invalid("Not a PEM file 😵".into())
-                                })
+                                    .unwrap_or_else(|error| {
+                                        Validation::Invalid(format!("{} 😣", error::describe(&error)).into())
+                                    })
+                            } else {
+                                Validation::Invalid("Not a private key 😣".into())
+                            }
                         })
                         //
                         .unwrap_or_else(|_| {
                             //
-                            Validation::Invalid("Failed to read from this file 😵‍💫".into())
-                        }),
-                )
-            };
+                            Validation::Invalid("Not a PEM file 😵".into())
+                        })
+                })
+                //
+                .unwrap_or_else(|_| {
+                    //
+                    Validation::Invalid("Failed to read from this file 😵‍💫".into())
+                }))
+        };
+
+        let Some(key) = Text::new("Please enter the path to your private key:")
+            //
+            .with_validator(key_validator)
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("tls private key path"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let key = fs::read_to_string(&key)
+            //
+            .map_err(FileError::on("read", &key))?;
+
+        let warnings = certificate::check_chain(&crt)
+            //
+            .map_err(ParametersError::certificate("the certificate is invalid"))?;
 
-            let Some(key) = Text::new("Please enter the path to your private key:")
+        for warning in warnings {
+            on_warning(Warning::Certificate(warning));
+        }
+
+        Ok(Some(Self(Some((crt, key)))))
+    }
+
+    fn inquire_generated(signed_by_local_ca: bool) -> Result<Option<Self>, ParametersError> {
+        let validator = |input: &str| -> Result<Validation, CustomUserError> {
+            Ok(certificate::parse_subject_alt_names(input)
                 //
-                .with_validator(key_validator)
+                .map(|_| Validation::Valid)
                 //
-                .prompt_skippable()
+                .unwrap_or_else(|error| Validation::Invalid(format!("{error} 😣").into())))
+        };
+
+        let Some(input) = Text::new("Which hostnames and IP addresses should the certificate cover?")
+            //
+            .with_default("localhost, 127.0.0.1")
+            //
+            .with_help_message("Separate them with commas.")
+            //
+            .with_validator(validator)
+            //
+            .prompt_skippable()
+            //
+            .map_err(ParametersError::prompt("certificate names"))?
+        //
+        else {
+            return Ok(None);
+        };
+
+        let subject_alt_names = certificate::parse_subject_alt_names(&input)
+            //
+            .map_err(ParametersError::certificate("the certificate names are invalid"))?;
+
+        let (crt, key) = if signed_by_local_ca {
+            certificate::generate_signed_by_local_ca(subject_alt_names)
+                //
+                .map_err(ParametersError::certificate(
+                    "failed to generate a certificate signed by the local CA",
+                ))?
+        } else {
+            certificate::generate_self_signed(subject_alt_names)
                 //
-                .context("failed to inquire the tls private key path")?
+                .map_err(ParametersError::certificate(
+                    "failed to generate a self-signed certificate",
+                ))?
+        };
+
+        Ok(Some(Self(Some((crt, key)))))
+    }
+
+    pub fn new(crt: String, key: String) -> Self {
+        Self(Some((crt, key)))
+    }
+
+    pub fn disabled() -> Self {
+        Self(None)
+    }
+
+    pub fn certificate(&self) -> Option<&str> {
+        self
             //
-            else {
-                return Ok(None);
-            };
+            .0
+            //
+            .as_ref()
+            //
+            .map(|(crt, _)| crt.as_str())
+    }
 
-            result = Self(Some((
+    pub fn without_key(&self) -> Self {
+        Self(
+            self
                 //
-                fs::read_to_string(crt)
-                    //
-                    .context("failed to read the certificate")?,
+                .0
                 //
-                fs::read_to_string(key)
-                    //
-                    .context("failed to read the private key")?,
-            )));
-        }
+                .as_ref()
+                //
+                .map(|(crt, _)| (crt.clone(), String::new())),
+        )
+    }
 
-        Ok(Some(result))
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
     }
 
     pub fn into_inner(self) -> Option<(String, String)> {
@@ -1,4 +1,4 @@
-use anyhow::*;
+use crate::error::RegistryError;
 
 pub const REGISTRY: &str = "ghcr.io";
 
@@ -6,7 +6,7 @@ pub const USERNAME: &str = "mayo-dayo";
 
 pub const REPOSITORY: &str = "app";
 
-async fn get_token() -> Result<String> {
+async fn get_token() -> reqwest::Result<String> {
     #[derive(serde::Deserialize)]
     struct Body {
         token: String,
@@ -25,25 +25,21 @@ async fn get_token() -> Result<String> {
 
     let Body { token } = reqwest::get(url)
         //
-        .await
-        //
-        .context("failed to send http request")?
+        .await?
         //
         .json::<Body>()
         //
-        .await
-        //
-        .context("failed to receive http response")?;
+        .await?;
 
     Ok(token)
 }
 
-pub async fn get_app_tags() -> Result<Vec<String>> {
+pub async fn get_app_tags() -> Result<Vec<String>, RegistryError> {
     let token = get_token()
         //
         .await
         //
-        .context("failed to get registry token")?;
+        .map_err(RegistryError::Token)?;
 
     #[derive(serde::Deserialize)]
     struct Body {
@@ -71,13 +67,13 @@ pub async fn get_app_tags() -> Result<Vec<String>> {
         //
         .await
         //
-        .context("failed to send http request")?
+        .map_err(RegistryError::Tags)?
         //
         .json::<Body>()
         //
         .await
         //
-        .context("failed to receive http response")?;
+        .map_err(RegistryError::Tags)?;
 
     Ok(tags)
 }
@@ -0,0 +1,353 @@
+//! Scheduled backups and their retention.
+//!
+//! Scheduled archives are named `<server>-<timestamp>.tar.zst`, with the time the backup was taken in UTC, e.g.
+//! `music-20260101T030000Z.tar.zst`. The newest archive in the target directory is the last run, so deleting archives
+//! by hand is enough to make a backup due again, and no other state needs to be kept.
+
+use crate::error::FileError;
+use crate::error::ScheduleError;
+
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Local;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+
+use croner::Cron;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+const EXTENSION: &str = ".tar.zst";
+
+/// Unfinished archives are written under this suffix, and renamed once complete.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// `backup::create` stages the data of an unfinished archive next to it, under this suffix in place of `.partial`.
+const STAGED_DATA_SUFFIX: &str = ".data.partial";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Schedule {
+    /// The `--host` the server was on when it was scheduled, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+
+    /// When to take backups, as a standard five-field cron expression in local time, e.g. `0 3 * * *`.
+    pub cron: String,
+
+    /// Where to write the archives.
+    pub directory: PathBuf,
+
+    #[serde(flatten)]
+    pub retention: Retention,
+}
+
+/// Which archives to keep, in the manner of `restic forget`: an archive is kept when any rule keeps it.
+///
+/// Without any rule, every archive is kept.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Retention {
+    /// Keep the newest archives.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+
+    /// Keep the newest archive of each of the last days that have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_daily: Option<usize>,
+
+    /// Keep the newest archive of each of the last weeks that have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_weekly: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Archive {
+    pub path: PathBuf,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}, {}", self.cron, self.directory.display(), self.retention)?;
+
+        if let Some(host) = &self.host {
+            write!(f, ", from {host}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = [
+            //
+            self.keep_last.map(|count| format!("last {count}")),
+            //
+            self.keep_daily.map(|count| format!("{count} daily")),
+            //
+            self.keep_weekly.map(|count| format!("{count} weekly")),
+        ]
+        //
+        .into_iter()
+        //
+        .flatten()
+        //
+        .collect::<Vec<_>>();
+
+        if rules.is_empty() {
+            f.write_str("keep all")
+        } else {
+            write!(f, "keep {}", rules.join(", "))
+        }
+    }
+}
+
+impl Schedule {
+    pub fn try_new(
+        host: Option<String>,
+        cron: String,
+        directory: PathBuf,
+        retention: Retention,
+    ) -> Result<Self, ScheduleError> {
+        let result = Self {
+            host,
+
+            cron,
+
+            directory,
+
+            retention,
+        };
+
+        result.parse_cron()?;
+
+        Ok(result)
+    }
+
+    fn parse_cron(&self) -> Result<Cron, ScheduleError> {
+        self
+            //
+            .cron
+            //
+            .parse::<Cron>()
+            //
+            .map_err(|source| ScheduleError::InvalidCron {
+                cron: self.cron.clone(),
+
+                source,
+            })
+    }
+
+    /// Whether a backup is due at `now`, i.e. whether the schedule had an occurrence since the last archive.
+    ///
+    /// A server that has never been backed up is always due.
+    pub fn is_due(&self, last: Option<&Archive>, now: DateTime<Local>) -> Result<bool, ScheduleError> {
+        let Some(last) = last else {
+            return Ok(true);
+        };
+
+        let cron = self.parse_cron()?;
+
+        let next = cron
+            //
+            .find_next_occurrence(&last.created_at.with_timezone(&Local), false)
+            //
+            .map_err(ScheduleError::NextOccurrence)?;
+
+        Ok(next <= now)
+    }
+
+    /// Path of a new archive of `server` taken at `now`.
+    pub fn archive_path(&self, server: &str, now: DateTime<Utc>) -> PathBuf {
+        self
+            //
+            .directory
+            //
+            .join(format!("{server}-{}{EXTENSION}", now.format(TIMESTAMP_FORMAT)))
+    }
+
+    /// Archives of `server` in the target directory, newest first.
+    pub fn archives(&self, server: &str) -> Result<Vec<Archive>, ScheduleError> {
+        let mut result = Vec::new();
+
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(result),
+
+            Err(error) => return Err(FileError::on("read", &self.directory)(error).into()),
+        };
+
+        let prefix = format!("{server}-");
+
+        for entry in entries {
+            let entry = entry
+                //
+                .map_err(FileError::on("read", &self.directory))?;
+
+            let file_name = entry.file_name();
+
+            let Some(timestamp) = file_name
+                //
+                .to_str()
+                //
+                .and_then(|file_name| file_name.strip_prefix(&prefix))
+                //
+                .and_then(|file_name| file_name.strip_suffix(EXTENSION))
+            else {
+                continue;
+            };
+
+            // the prefix of another server's archives can be this one's, e.g. `music-old-...` for `music`
+            let Ok(created_at) = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) else {
+                continue;
+            };
+
+            result.push(Archive {
+                path: entry.path(),
+
+                created_at: created_at.and_utc(),
+            });
+        }
+
+        result.sort_unstable_by_key(|archive| Reverse(archive.created_at));
+
+        Ok(result)
+    }
+
+    /// Deletes the unfinished archives of `server` and their staged data, left behind by interrupted runs.
+    pub fn remove_partial_archives(&self, server: &str) -> Result<(), ScheduleError> {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return Ok(());
+        };
+
+        let prefix = format!("{server}-");
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+
+            let Some(timestamp) = file_name
+                //
+                .to_str()
+                //
+                .and_then(|file_name| file_name.strip_prefix(&prefix))
+                //
+                .and_then(|file_name| {
+                    file_name
+                        //
+                        .strip_suffix(STAGED_DATA_SUFFIX)
+                        //
+                        .or_else(|| file_name.strip_suffix(PARTIAL_SUFFIX))
+                })
+                //
+                .and_then(|file_name| file_name.strip_suffix(EXTENSION))
+            else {
+                continue;
+            };
+
+            // as for archives, `music-old-...` belongs to another server than `music`
+            if NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).is_ok() {
+                remove(&entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the archives of `server` that no retention rule keeps, and returns their paths.
+    pub fn prune(&self, server: &str) -> Result<Vec<PathBuf>, ScheduleError> {
+        let archives = self.archives(server)?;
+
+        let kept = self.retention.keep(&archives);
+
+        let mut removed = Vec::new();
+
+        for (index, archive) in archives.into_iter().enumerate() {
+            if kept.contains(&index) {
+                continue;
+            }
+
+            remove(&archive.path)?;
+
+            removed.push(archive.path);
+        }
+
+        Ok(removed)
+    }
+}
+
+impl Retention {
+    pub fn is_empty(&self) -> bool {
+        self.keep_last.is_none() && self.keep_daily.is_none() && self.keep_weekly.is_none()
+    }
+
+    /// Indices of the archives to keep, given archives sorted newest first.
+    fn keep(&self, archives: &[Archive]) -> BTreeSet<usize> {
+        if self.is_empty() {
+            return (0..archives.len()).collect();
+        }
+
+        let mut result = BTreeSet::new();
+
+        if let Some(count) = self.keep_last {
+            result.extend((0..archives.len()).take(count));
+        }
+
+        let mut keep_newest_per_period = |count: Option<usize>, period: &dyn Fn(DateTime<Local>) -> (i32, u32)| {
+            let Some(count) = count else {
+                return;
+            };
+
+            let mut periods = BTreeSet::new();
+
+            for (index, archive) in archives.iter().enumerate() {
+                if periods.len() == count {
+                    break;
+                }
+
+                // archives are sorted newest first, so the first one of a period is its newest
+                if periods.insert(period(archive.created_at.with_timezone(&Local))) {
+                    result.insert(index);
+                }
+            }
+        };
+
+        keep_newest_per_period(self.keep_daily, &|time| (time.year(), time.ordinal()));
+
+        keep_newest_per_period(self.keep_weekly, &|time| {
+            let week = time.iso_week();
+
+            (week.year(), week.week())
+        });
+
+        result
+    }
+}
+
+/// Path under which an archive is written until it is complete.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut result = path.as_os_str().to_owned();
+
+    result.push(PARTIAL_SUFFIX);
+
+    PathBuf::from(result)
+}
+
+fn remove(path: &Path) -> Result<(), ScheduleError> {
+    fs::remove_file(path)
+        //
+        .map_err(FileError::on("remove", path))?;
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
+use crate::error;
+use crate::error::ServerError;
 use crate::labels::*;
 use crate::parameters::Parameters;
 
 use std::cmp::Ordering;
+use std::fmt;
 
 use bollard::models::ContainerSummary;
 
@@ -14,7 +17,35 @@ pub struct Server {
 
     pub state: String,
 
+    pub health: Option<String>,
+
     pub parameters: Parameters,
+
+    /// Schema of the parameters label, which is newer than [`CURRENT_SCHEMA`] for servers of a newer CLI.
+    pub schema: u32,
+
+    /// Name of the server this one was cloned from.
+    pub origin: Option<String>,
+}
+
+impl Server {
+    /// Servers of a newer schema are only shown, since rewriting their label would lose what this CLI cannot read.
+    pub fn is_read_only(&self) -> bool {
+        self.schema > CURRENT_SCHEMA
+    }
+
+    /// Refuses to write the parameters of a read-only server again, e.g. into a new container or a backup.
+    pub fn check_writable(&self) -> Result<(), ServerError> {
+        if self.is_read_only() {
+            return Err(ServerError::ReadOnly {
+                name: self.parameters.name.to_string(),
+
+                schema: self.schema,
+            });
+        }
+
+        Ok(())
+    }
 }
 
 impl Eq for Server {
@@ -35,12 +66,12 @@ impl Ord for Server {
 
 impl PartialOrd for Server {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.parameters.name.partial_cmp(&other.parameters.name)
+        Some(self.cmp(other))
     }
 }
 
 impl TryFrom<ContainerSummary> for Server {
-    type Error = ();
+    type Error = ServerError;
 
     fn try_from(summary: ContainerSummary) -> Result<Self, Self::Error> {
         let ContainerSummary {
@@ -49,30 +80,53 @@ impl TryFrom<ContainerSummary> for Server {
             //
             state,
             //
+            status,
+            //
             labels,
             //
             ..
         } = summary;
 
-        let id = id.ok_or(())?;
+        let id = id.ok_or(ServerError::MissingField("id"))?;
 
-        let state = state.ok_or(())?;
+        let state = state.ok_or(ServerError::MissingField("state"))?;
+
+        // the summary carries no health object, only a status such as "Up 5 minutes (healthy)" or "Up 1 hour (Paused)"
+        let health = status
+            //
+            .as_deref()
+            //
+            .and_then(|status| status.rsplit_once('('))
+            //
+            .and_then(|(_, health)| health.strip_suffix(')'))
+            //
+            .map(|health| health.trim_start_matches("health: "))
+            //
+            .filter(|health| matches!(*health, "healthy" | "unhealthy" | "starting"))
+            //
+            .map(str::to_string);
+
+        let labels = labels.ok_or(ServerError::MissingField("labels"))?;
 
         let parameters = labels
             //
-            .map(|labels| {
-                labels
-                    //
-                    .get(LABEL_KEY_PARAMETERS)
-                    //
-                    .map(|value| Parameters::try_from(value.as_str()).ok())
-                    //
-                    .flatten()
-            })
+            .get(LABEL_KEY_PARAMETERS)
             //
-            .flatten()
+            .ok_or(ServerError::MissingLabel(LABEL_KEY_PARAMETERS))?;
+
+        let DecodedParameters { schema, parameters } = decode_parameters(parameters)
             //
-            .ok_or(())?;
+            .map_err(|source| ServerError::MalformedLabel {
+                label: LABEL_KEY_PARAMETERS,
+
+                source,
+            })?;
+
+        let origin = labels
+            //
+            .get(LABEL_KEY_ORIGIN)
+            //
+            .cloned();
 
         Ok(Self {
             //
@@ -80,7 +134,84 @@ impl TryFrom<ContainerSummary> for Server {
             //
             state,
             //
+            health,
+            //
             parameters,
+            //
+            schema,
+            //
+            origin,
         })
     }
 }
+
+/// A container that carries the labels of a server, but that this CLI cannot manage.
+#[derive(Debug)]
+pub struct HiddenContainer {
+    pub id: String,
+
+    /// Name of the container, without the `mayo-` prefix of servers.
+    pub name: String,
+
+    pub state: String,
+
+    pub reason: HiddenReason,
+}
+
+impl HiddenContainer {
+    pub fn new(summary: &ContainerSummary, reason: HiddenReason) -> Self {
+        let name = summary
+            //
+            .names
+            //
+            .iter()
+            //
+            .flatten()
+            //
+            .next()
+            //
+            .map(|name| name.trim_start_matches('/'))
+            //
+            .map(|name| name.strip_prefix("mayo-").unwrap_or(name).to_string())
+            //
+            .unwrap_or_else(|| "-".to_string());
+
+        Self {
+            id: summary.id.clone().unwrap_or_default(),
+
+            name,
+
+            state: summary.state.clone().unwrap_or_else(|| "-".to_string()),
+
+            reason,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HiddenReason {
+    /// None of the image's tags is a compatible app version.
+    IncompatibleImage(Vec<String>),
+
+    /// The image has no tags left, e.g. because a newer image took them over.
+    UntaggedImage,
+
+    /// The parameters are in a schema of a newer CLI, and this CLI cannot even read them.
+    NewerSchema(u32),
+
+    CorruptParameters(ServerError),
+}
+
+impl fmt::Display for HiddenReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IncompatibleImage(tags) => write!(f, "incompatible image {}", tags.join(", ")),
+
+            Self::UntaggedImage => f.write_str("untagged image"),
+
+            Self::NewerSchema(schema) => write!(f, "parameters in newer schema {schema}"),
+
+            Self::CorruptParameters(error) => write!(f, "corrupt parameters: {}", error::describe(error)),
+        }
+    }
+}
@@ -1,7 +1,6 @@
+use crate::error::RegistryError;
 use crate::registry;
 
-use anyhow::*;
-
 use semver::*;
 
 fn tag_to_version_or_none<T>(tag: T) -> Option<Version>
@@ -13,7 +12,7 @@ where
     Version::parse(tag_as_ref).ok()
 }
 
-fn is_compatible_app_version(version: &Version) -> bool {
+pub fn is_compatible_app_version(version: &Version) -> bool {
     // ^0.3
     const COMPARATOR: Comparator = Comparator {
         op: Op::Caret,
@@ -46,12 +45,8 @@ where
         .filter(is_compatible_app_version)
 }
 
-pub async fn get_latest_compatible_app_version() -> Result<Version> {
-    let tags = registry::get_app_tags()
-        //
-        .await
-        //
-        .context("failed to get registry tags")?;
+pub async fn get_latest_compatible_app_version() -> Result<Version, RegistryError> {
+    let tags = registry::get_app_tags().await?;
 
     let mut versions = tags_to_compatible_app_versions(tags).collect::<Vec<_>>();
 
@@ -61,7 +56,7 @@ pub async fn get_latest_compatible_app_version() -> Result<Version> {
         //
         .pop()
         //
-        .expect("the registry should contain at least one compatible version");
+        .ok_or(RegistryError::NoCompatibleVersion)?;
 
     Ok(latest_version)
 }